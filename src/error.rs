@@ -29,6 +29,171 @@ pub enum VoteError {
 
     #[error("Wrong settings PDA")]
     WrongSettingsPDA,
+
+    #[error("Settings authority signature is required")]
+    AuthorityRequired,
+
+    #[error("Trying to create poll with wrong options")]
+    WrongPollOptions,
+
+    #[error("Trying to vote for non-existed option")]
+    WrongOptionIndex,
+
+    #[error("Trying to create vote with wrong pass threshold")]
+    WrongThreshold,
+
+    #[error("Trying to close already closed vote")]
+    DoubleClose,
+
+    #[error("Trying to close alive vote")]
+    CloseAliveVote,
+
+    #[error("Trying to create vote that ends before it starts")]
+    WrongVoteWindow,
+
+    #[error("Trying to participate in vote that is not started")]
+    VoteNotStarted,
+
+    #[error("Trying to participate in ended vote")]
+    VoteEnded,
+
+    #[error("Trying to finalize vote before its lifetime elapsed")]
+    VoteNotExpired,
+
+    #[error("Trying to create realm with wrong name")]
+    WrongRealmName,
+
+    #[error("Vote belongs to another realm")]
+    WrongRealm,
+
+    #[error("Wrong token account")]
+    WrongTokenAccount,
+
+    #[error("Trying to vote without voting power")]
+    NoVotingPower,
+
+    #[error("Wrong voter record PDA")]
+    WrongVoterRecordPDA,
+
+    #[error("Trying to withdraw while participating in not closed votes")]
+    WithdrawWithActiveVotes,
+
+    #[error("Trying to withdraw more than deposited")]
+    InsufficientDeposit,
+
+    #[error("Trying to lock deposit longer than max lockup")]
+    WrongLockup,
+
+    #[error("Trying to withdraw locked deposit")]
+    DepositLocked,
+
+    #[error("Ballot does not match voting mode of vote")]
+    WrongVotingMode,
+
+    #[error("Trying to spend more voice credits than budget")]
+    CreditBudgetExceeded,
+
+    #[error("Vote tally overflow")]
+    TallyOverflow,
+
+    #[error("Wrong ranking of poll options")]
+    WrongRanking,
+
+    #[error("Trying to tally vote that is not waiting for tally")]
+    NotTallying,
+
+    #[error("Wrong approvals bitmask of poll options")]
+    WrongApprovals,
+
+    #[error("Wrong scores of poll options")]
+    WrongScores,
+
+    #[error("Trying to retract ballot that was not cast")]
+    NotVoted,
+
+    #[error("Voter is not in allowlist of vote")]
+    NotAllowlisted,
+
+    #[error("Voter is not a member of vote")]
+    NotMember,
+
+    #[error("Wrong membership PDA")]
+    WrongMembershipPDA,
+
+    #[error("Trying to add voter who is already a member")]
+    DoubleMember,
+
+    #[error("Vote combines gating options that can't be used together")]
+    WrongGating,
+
+    #[error("Voter does not hold the NFT")]
+    NotNftHolder,
+
+    #[error("Wrong NFT metadata account")]
+    WrongMetadata,
+
+    #[error("NFT is not verified member of vote collection")]
+    WrongCollection,
+
+    #[error("Wrong delegation PDA")]
+    WrongDelegationPDA,
+
+    #[error("Voter did not delegate to this user")]
+    NotDelegate,
+
+    #[error("Trying to delegate to yourself")]
+    SelfDelegation,
+
+    #[error("Vote does not accept delegated ballots")]
+    DelegationNotAllowed,
+
+    #[error("Trying to reveal ballot outside of reveal window")]
+    RevealNotOpen,
+
+    #[error("Revealed ballot does not match commitment")]
+    WrongReveal,
+
+    #[error("Nullifier does not match voter key")]
+    WrongNullifier,
+
+    #[error("Wrong anonymous ballot verifier")]
+    WrongVerifier,
+
+    #[error("Relayed ballot is not signed by voter")]
+    WrongRelaySignature,
+
+    #[error("Relayed ballot nonce was already used")]
+    StaleNonce,
+
+    #[error("Wrong proposal transaction PDA")]
+    WrongProposalTransactionPDA,
+
+    #[error("Wrong governance PDA")]
+    WrongGovernancePDA,
+
+    #[error("Trying to change proposal of vote that already has ballots")]
+    ProposalHasBallots,
+
+    #[error("Trying to execute proposal of vote that did not pass or is timelocked")]
+    NotExecutable,
+
+    #[error("Trying to execute proposal transaction twice")]
+    DoubleExecute,
+
+    #[error("Trying to close vote with proposal transactions not closed")]
+    OpenProposalTransactions,
+
+    #[error("Trying to close proposal transaction that can still be executed")]
+    ProposalTransactionPending,
+}
+
+/// `VoteMany` fails with custom error of this offset plus index of failed ballot,
+/// the ballot's own error is logged
+pub const BATCH_ENTRY_ERROR: u32 = 0x1000;
+
+/// Index of failed `VoteMany` ballot encoded in custom error `code`
+pub fn batch_entry_index(code: u32) -> Option<usize> {
+    code.checked_sub(BATCH_ENTRY_ERROR).map(|index| index as usize)
 }
 
 impl From<VoteError> for ProgramError {
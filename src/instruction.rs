@@ -1,5 +1,7 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
+    ed25519_program,
+    hash::hashv,
     instruction::{AccountMeta, Instruction},
     pubkey::Pubkey,
     system_program, sysvar,
@@ -7,88 +9,582 @@ use solana_program::{
 
 use crate::{
     id,
-    state::{UserVotes, Vote, VoteCounter},
+    metadata::Metadata,
+    state::{
+        allowlist_leaf, allowlist_node, anonymous_nullifier, BallotKind, Delegation, Membership,
+        ProposalInstruction, ProposalTransaction, Settings, UserVotes, Vote, VoteConfig,
+        VoteCounter, VoterRecord,
+    },
 };
 
+/// Domain tag of messages signed for `RelayVote`
+pub const RELAY_TAG: &[u8] = b"voting:relay-vote";
+
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
 pub enum VoteInstruction {
-    /// Participate in vote.
+    /// Participate in vote, or replace ballot cast earlier while vote is alive.
+    /// Accounts from 6 are passed only if vote needs them, keeping their order.
+    /// After them the same ballot is cast for every delegator of user passed as: delegator,
+    /// its delegation PDA, its writable `UserVotes` PDA and, if vote is token-weighted,
+    /// its writable voter record.
     /// Accounts:
-    /// 0. `[signer]` want to vote
-    /// 1. `[writable]` contain info about vote that this user participate in, PDA
-    /// 2. `[]` concrete vote, PDA
+    /// 0. `[signer, writable]` want to vote
+    /// 1. `[writable]` contain info about vote that this user participate in, PDA,
+    ///    derived from NFT mint instead of user if vote is collection-gated
+    /// 2. `[writable]` concrete vote, PDA
     /// 3. `[]` Rent sysvar
     /// 4. `[]` System program
+    /// 5. `[]` Clock sysvar
+    /// 6. `[]` NFT token account of user, if vote is collection-gated
+    /// 7. `[]` metadata of NFT, if vote is collection-gated
+    /// 8. `[writable]` voter record of user for governing mint, PDA, if vote is token-weighted
+    /// 9. `[]` membership of user, PDA, if vote is members-only
     Vote { direction: Direction },
 
     /// Create a vote.
     /// Accounts:
     /// 0. `[signer]` admin
     /// 1. '[writable]' vote to create, PDA
-    /// 2. '[writable]' vote counter, PDA
+    /// 2. '[writable]' vote counter of realm, PDA
     /// 3. `[]` Rent sysvar, PDA
     /// 4. `[]` System program, PDA
     /// 5. '[]' Clock, PDA
-    CreateVote { vote_seed: Pubkey },
+    CreateVote { vote_seed: Pubkey, config: VoteConfig },
 
     /// Delete a vote.
     /// Accounts:
-    /// 0. `[signer]` admin
+    /// 0. `[signer]` admin, or anyone once vote end time passed
     /// 1. `[writable]` vote to delete, PDA
-    /// 2. '[writable]' vote counter, PDA
+    /// 2. '[writable]' vote counter of vote realm, PDA
     /// 3. '[]' Clock, PDA
     DeleteVote { admin: [u8; 32] },
 
-    /// Create vote counter.
+    /// Create realm with its own vote counter.
     /// Accounts:
-    /// 0. `[signer]` admin
-    /// 2. '[writable]' vote counter, PDA
+    /// 0. `[signer]` admin, becomes settings authority
+    /// 2. '[writable]' vote counter of realm, PDA
     /// 3. `[]` Rent sysvar, PDA
     /// 4. `[]` System program, PDA
-    CreateVoteCounter,
+    CreateRealm { name: String, settings: Settings },
+
+    /// Update settings stored in vote counter.
+    /// Accounts:
+    /// 0. `[signer]` settings authority
+    /// 1. '[writable]' vote counter of realm, PDA
+    UpdateSettings { settings: Settings },
+
+    /// Close not alive vote whose proposal transactions are closed, and return its rent.
+    /// Vote PDA is left as empty tombstone so its address can't be reused by a new vote
+    /// that would pick up ballots of the closed one.
+    /// Accounts:
+    /// 0. `[signer]` admin
+    /// 1. `[writable]` vote to close, PDA
+    /// 2. `[writable]` recipient of vote lamports
+    /// 3. `[]` Rent sysvar
+    CloseVote,
+
+    /// Close info about user participation in not alive or closed vote and return its rent.
+    /// Accounts:
+    /// 0. `[signer, writable]` user who voted
+    /// 1. `[writable]` contain info about vote that this user participate in, PDA
+    /// 2. `[]` concrete vote, PDA
+    /// 3. `[]` NFT token account of user, if ballot was cast by NFT
+    /// 4. `[writable]` voter record the ballot weight was taken from, PDA, if vote is token-weighted
+    CloseUserVote,
+
+    /// Close vote whose lifetime elapsed, can be called by anyone once vote end time passed.
+    /// Vote without end time can be closed only by admin until realm max lifetime elapsed.
+    /// Accounts:
+    /// 0. `[signer]` anyone, recorded as vote closer
+    /// 1. `[writable]` vote to finalize, PDA
+    /// 2. '[writable]' vote counter of vote realm, PDA
+    /// 3. '[]' Clock, PDA
+    Finalize,
+
+    /// Participate in quadratic vote, counted with square root of spent voice credits.
+    /// Accounts are the same as for `Vote`.
+    VoteWithCredits { direction: Direction, credits: u64 },
+
+    /// Deposit governing tokens to escrow of voter record, creates both on first deposit.
+    /// Non-zero `lockup_secs` extends lockup of whole deposit to at least that long from now.
+    /// Accounts:
+    /// 0. `[signer, writable]` voter
+    /// 1. `[writable]` voter record, PDA
+    /// 2. `[writable]` escrow token account, PDA
+    /// 3. `[writable]` voter token account to take tokens from
+    /// 4. `[]` governing mint
+    /// 5. `[]` Rent sysvar
+    /// 6. `[]` System program
+    /// 7. `[]` Token program
+    /// 8. `[]` Clock sysvar
+    Deposit { amount: u64, lockup_secs: u64 },
+
+    /// Withdraw governing tokens from escrow, when voter has no ballots in not closed votes
+    /// and lockup ended.
+    /// Accounts:
+    /// 0. `[signer]` voter
+    /// 1. `[writable]` voter record, PDA
+    /// 2. `[writable]` escrow token account, PDA
+    /// 3. `[writable]` token account to send tokens to
+    /// 4. `[]` Token program
+    /// 5. `[]` Clock sysvar
+    Withdraw { amount: u64 },
+
+    /// Count batch of ranked ballots in current instant runoff round, can be called by anyone.
+    /// Ends the round once every ballot is counted, so may need several transactions per round.
+    /// Accounts:
+    /// 0. `[writable]` ranked vote in `Tallying` status, PDA
+    /// 1.. pairs of:
+    ///     `[]` user who voted, or nullifier as pubkey in anonymous vote
+    ///     `[writable]` contain info about vote that this user participate in, PDA
+    Tally,
+
+    /// Withdraw ballot from alive vote, user can vote again later.
+    /// Accounts:
+    /// 0. `[signer]` user who voted
+    /// 1. `[writable]` contain info about vote that this user participate in, PDA
+    /// 2. `[writable]` concrete vote, PDA
+    /// 3. `[]` Clock sysvar
+    /// 4. `[]` NFT token account of user, if ballot was cast by NFT
+    /// 5. `[writable]` voter record the ballot weight was taken from, PDA, if vote is token-weighted
+    RetractVote,
+
+    /// Participate in vote with allowlist, proving membership of user.
+    /// `credits` are set for quadratic vote, as in `VoteWithCredits`.
+    /// Accounts are the same as for `Vote`.
+    VoteWithProof { direction: Direction, credits: Option<u64>, proof: VoterProof },
+
+    /// Make voter a member of members-only vote.
+    /// Accounts:
+    /// 0. `[signer, writable]` vote admin or realm authority, pays for membership
+    /// 1. `[]` vote, PDA
+    /// 2. `[]` vote counter of vote realm, PDA
+    /// 3. `[writable]` membership to create, PDA
+    /// 4. `[]` Rent sysvar
+    /// 5. `[]` System program
+    AddVoter { voter: Pubkey },
+
+    /// Remove voter from members of vote and return membership rent, cast ballot stays counted.
+    /// Accounts:
+    /// 0. `[signer, writable]` vote admin or realm authority, receives rent
+    /// 1. `[]` vote, PDA
+    /// 2. `[]` vote counter of vote realm, PDA
+    /// 3. `[writable]` membership to close, PDA
+    RemoveVoter { voter: Pubkey },
+
+    /// Let `delegate` cast ballots for signer in votes of realm, replaces earlier delegation.
+    /// Ballot cast by delegator personally is never overwritten by delegate.
+    /// Accounts:
+    /// 0. `[signer, writable]` delegator
+    /// 1. `[]` vote counter of realm, PDA
+    /// 2. `[writable]` delegation, PDA
+    /// 3. `[]` Rent sysvar
+    /// 4. `[]` System program
+    Delegate { delegate: Pubkey },
+
+    /// Close delegation and return its rent, ballots already cast by delegate stay counted.
+    /// Accounts:
+    /// 0. `[signer, writable]` delegator
+    /// 1. `[]` vote counter of realm, PDA
+    /// 2. `[writable]` delegation, PDA
+    RevokeDelegation,
+
+    /// Reveal committed ballot of commit-reveal vote and count it, after vote end time
+    /// and before its reveal end time.
+    /// Accounts:
+    /// 0. `[signer]` user who committed
+    /// 1. `[writable]` contain info about vote that this user participate in, PDA
+    /// 2. `[writable]` concrete vote, PDA
+    /// 3. `[]` Clock sysvar
+    /// 4. `[]` NFT token account of user, if ballot was cast by NFT
+    Reveal { direction: Direction, salt: [u8; 32] },
+
+    /// Participate in anonymous vote, ballot is recorded by `nullifier` so it can be cast once
+    /// and is not linked to voter. Ballot can't be changed or retracted, payer can reclaim
+    /// rent of nullifier PDA with `CloseNullifier` once vote is not alive.
+    /// Accounts:
+    /// 0. `[signer, writable]` payer, e.g. relayer or fresh wallet
+    /// 1. `[writable]` contain info about ballot, nullifier PDA
+    /// 2. `[writable]` concrete vote, PDA
+    /// 3. `[]` Rent sysvar
+    /// 4. `[]` System program
+    /// 5. `[]` Clock sysvar
+    /// 6. `[signer]` voter key for `AnonymousProof::Signer`,
+    ///    or `[]` verifier program for `AnonymousProof::Verifier`
+    VoteAnonymous { direction: Direction, nullifier: [u8; 32], proof: AnonymousProof },
+
+    /// Participate in vote with ballot signed off-chain by `voter` and submitted by relayer who
+    /// pays for it. Instruction must be preceded by Ed25519 program instruction verifying
+    /// signature of `voter` over `Direction::relay_message`, `nonce` must be greater than nonce
+    /// of ballot relayed earlier. Optional accounts follow as in `Vote`.
+    /// Accounts:
+    /// 0. `[signer, writable]` relayer
+    /// 1. `[writable]` contain info about vote that voter participate in, PDA
+    /// 2. `[writable]` concrete vote, PDA
+    /// 3. `[]` Rent sysvar
+    /// 4. `[]` System program
+    /// 5. `[]` Clock sysvar
+    /// 6. `[]` Instructions sysvar
+    RelayVote { voter: Pubkey, direction: Direction, nonce: u64 },
+
+    /// Participate in several votes at once, each `(vote, direction)` of `ballots` is cast as
+    /// `Vote` with no optional accounts, so token-weighted, collection-gated and members-only
+    /// votes are not supported. Batch fails as a whole with `BATCH_ENTRY_ERROR` plus index
+    /// of failed ballot, the ballot's own error is logged.
+    /// Accounts:
+    /// 0. `[signer, writable]` want to vote
+    /// 1. `[]` Rent sysvar
+    /// 2. `[]` System program
+    /// 3. `[]` Clock sysvar
+    /// 4. `[writable]` `UserVotes` PDA of first ballot
+    /// 5. `[writable]` vote of first ballot, PDA
+    /// 6. etc. for every next ballot
+    VoteMany { ballots: Vec<(Pubkey, Direction)> },
+
+    /// Attach instruction the vote executes once passed, before any ballot is cast. Realm
+    /// authority approves it, as it is signed by governance PDA of realm. Only For/Against
+    /// vote in `Simple` mode can carry instructions.
+    /// Accounts:
+    /// 0. `[signer, writable]` admin of vote
+    /// 1. `[signer]` settings authority of realm
+    /// 2. `[]` vote counter of realm, PDA
+    /// 3. `[writable]` concrete vote, PDA
+    /// 4. `[writable]` proposal transaction with `index`, PDA
+    /// 5. `[]` Rent sysvar
+    /// 6. `[]` System program
+    AddProposalTransaction { index: u16, instruction: ProposalInstruction },
+
+    /// Run proposal transaction of passed vote once its execution delay elapsed, signed by
+    /// governance PDA of realm. Anyone can execute, every transaction runs once.
+    /// Accounts:
+    /// 0. `[]` concrete vote, PDA
+    /// 1. `[]` governance of realm, PDA
+    /// 2. `[writable]` proposal transaction, PDA
+    /// 3. `[]` Clock sysvar
+    /// 4. `[]` program and accounts of the instruction
+    Execute,
+
+    /// Close proposal transaction once executed, once its vote ended without passing,
+    /// or cancel it once `EXECUTION_GRACE_SECS` after timelock elapsed, and return its rent
+    /// to signer. Vote can be closed only after all its transactions.
+    /// Accounts:
+    /// 0. `[signer, writable]` admin of vote or authority of its realm
+    /// 1. `[]` vote counter of vote realm, PDA
+    /// 2. `[writable]` concrete vote, PDA
+    /// 3. `[writable]` proposal transaction, PDA
+    /// 4. `[]` Clock sysvar
+    CloseProposalTransaction,
+
+    /// Close nullifier PDA of anonymous ballot in not alive or closed vote and return its rent.
+    /// Accounts:
+    /// 0. `[signer, writable]` payer of anonymous ballot
+    /// 1. `[writable]` contain info about ballot, nullifier PDA
+    /// 2. `[]` concrete vote, PDA
+    CloseNullifier { nullifier: [u8; 32] },
+}
+
+/// Proof that anonymous ballot belongs to voter of `VoteConfig::anonymous_root`
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub enum AnonymousProof {
+    /// Voter key signs the instruction, its `anonymous_leaf` is proven by Merkle `path` and
+    /// nullifier must be `anonymous_nullifier` of it. Pseudonymous only, every ballot of
+    /// the key is linkable to it.
+    Signer { path: Vec<[u8; 32]> },
+    /// Zero-knowledge `proof`, checked by calling `VoteConfig::anonymous_verifier`
+    /// with `AnonymousStatement` as instruction data
+    Verifier { proof: Vec<u8> },
+}
+
+/// Statement the anonymous verifier program must fail on unless `proof` shows that
+/// `nullifier` was derived from secret of a commitment in `root` for `vote`, binding `ballot`
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct AnonymousStatement {
+    pub root: [u8; 32],
+    pub nullifier: [u8; 32],
+    pub vote: Pubkey,
+    /// Hash of serialized `Direction`
+    pub ballot: [u8; 32],
+    pub proof: Vec<u8>,
+}
+
+/// Merkle proof that `allowlist_leaf` of user and `weight` is in `VoteConfig::voter_root`
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct VoterProof {
+    pub weight: u64,
+
+    /// Sibling nodes from leaf up to root
+    pub path: Vec<[u8; 32]>,
+}
+
+impl VoterProof {
+    pub fn verify(&self, root: &[u8; 32], voter: &Pubkey) -> bool {
+        let leaf = allowlist_leaf(voter, self.weight);
+        let node = self.path.iter().fold(leaf, |node, sibling| allowlist_node(&node, sibling));
+        node == *root
+    }
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
 pub enum Direction {
     For,
     Against,
+    /// Index of poll option
+    Choice(u8),
+    Abstain,
+    /// Indices of poll options in order of preference, for ranked vote
+    Ranking(Vec<u8>),
+    /// Bitmask of approved poll options, bit `i` approves option `i`, for approval vote
+    Approve(u16),
+    /// Score of every poll option in order, for score vote
+    Scores(Vec<u8>),
+    /// `Direction::commitment` of secret ballot, for commit-reveal vote
+    Commit([u8; 32]),
+}
+
+impl Direction {
+    pub fn ballot_kind(&self) -> BallotKind {
+        match self {
+            Direction::For => BallotKind::For,
+            Direction::Against => BallotKind::Against,
+            Direction::Choice(_) => BallotKind::Choice,
+            Direction::Abstain => BallotKind::Abstain,
+            Direction::Ranking(_) => BallotKind::Ranking,
+            Direction::Approve(_) => BallotKind::Approve,
+            Direction::Scores(_) => BallotKind::Scores,
+            Direction::Commit(_) => BallotKind::Commit,
+        }
+    }
+
+    /// Hash hiding ballot until it is revealed with the same `salt`, bound to `vote` and
+    /// `voter` so commitment copied by another voter can't be revealed. `voter` is the key
+    /// `UserVotes` PDA is derived from, NFT mint for collection-gated vote.
+    pub fn commitment(&self, vote: &Pubkey, voter: &Pubkey, salt: &[u8; 32]) -> [u8; 32] {
+        let direction = self.try_to_vec().unwrap_or_default();
+        hashv(&[&vote.to_bytes(), &voter.to_bytes(), &direction, salt]).to_bytes()
+    }
+
+    /// Message voter signs to have ballot relayed to `vote`, prefixed with `RELAY_TAG` and
+    /// program id so signature made for anything else can't be relayed as ballot
+    pub fn relay_message(&self, vote: &Pubkey, nonce: u64) -> Vec<u8> {
+        let mut message = RELAY_TAG.to_vec();
+        message.extend_from_slice(id().as_ref());
+        message.extend_from_slice(&(vote, self, nonce).try_to_vec().unwrap_or_default());
+        message
+    }
 }
 
 impl VoteInstruction {
-    pub fn delete(admin: &Pubkey, vote: &Pubkey) -> Instruction {
-        let (vote_counter_pubkey, _) = VoteCounter::get_vote_pubkey_with_bump();
+    pub fn delete(admin: &Pubkey, realm: &Pubkey, vote: &Pubkey) -> Instruction {
         Instruction::new_with_borsh(
             id(),
             &VoteInstruction::DeleteVote { admin: admin.to_bytes() },
             vec![
                 AccountMeta::new_readonly(*admin, true),
                 AccountMeta::new(*vote, false),
-                AccountMeta::new(vote_counter_pubkey, false),
+                AccountMeta::new(*realm, false),
                 AccountMeta::new_readonly(sysvar::clock::id(), false),
             ],
         )
     }
 
     pub fn vote(user: &Pubkey, vote: &Pubkey, direction: Direction) -> Instruction {
-        let user_votes_pubkey = UserVotes::get_uservote_pubkey(user, vote);
         Instruction::new_with_borsh(
             id(),
             &VoteInstruction::Vote { direction: (direction) },
+            Self::vote_accounts(user, vote),
+        )
+    }
+
+    pub fn vote_with_credits(
+        user: &Pubkey,
+        vote: &Pubkey,
+        direction: Direction,
+        credits: u64,
+    ) -> Instruction {
+        Instruction::new_with_borsh(
+            id(),
+            &VoteInstruction::VoteWithCredits { direction, credits },
+            Self::vote_accounts(user, vote),
+        )
+    }
+
+    /// Vote with NFT held in `token_account`, ballot is keyed by `nft_mint`
+    pub fn vote_with_nft(
+        user: &Pubkey,
+        vote: &Pubkey,
+        nft_mint: &Pubkey,
+        token_account: &Pubkey,
+        direction: Direction,
+    ) -> Instruction {
+        let mut instruction = Self::vote(nft_mint, vote, direction);
+        instruction.accounts[0] = AccountMeta::new(*user, true);
+        instruction.accounts.push(AccountMeta::new_readonly(*token_account, false));
+        instruction
+            .accounts
+            .push(AccountMeta::new_readonly(Metadata::get_metadata_pubkey(nft_mint), false));
+        instruction
+    }
+
+    pub fn retract_vote_with_nft(
+        user: &Pubkey,
+        vote: &Pubkey,
+        nft_mint: &Pubkey,
+        token_account: &Pubkey,
+    ) -> Instruction {
+        let mut instruction = Self::retract_vote(nft_mint, vote);
+        instruction.accounts[0] = AccountMeta::new_readonly(*user, true);
+        instruction.accounts.push(AccountMeta::new_readonly(*token_account, false));
+        instruction
+    }
+
+    pub fn close_user_vote_with_nft(
+        user: &Pubkey,
+        vote: &Pubkey,
+        nft_mint: &Pubkey,
+        token_account: &Pubkey,
+    ) -> Instruction {
+        let mut instruction = Self::close_user_vote(nft_mint, vote);
+        instruction.accounts[0] = AccountMeta::new(*user, true);
+        instruction.accounts.push(AccountMeta::new_readonly(*token_account, false));
+        instruction
+    }
+
+    /// Vote for user and every delegator in not token-weighted vote
+    pub fn vote_for_delegators(
+        user: &Pubkey,
+        realm: &Pubkey,
+        vote: &Pubkey,
+        direction: Direction,
+        delegators: &[Pubkey],
+    ) -> Instruction {
+        let mut instruction = Self::vote(user, vote, direction);
+        for delegator in delegators {
+            instruction.accounts.extend([
+                AccountMeta::new_readonly(*delegator, false),
+                AccountMeta::new_readonly(
+                    Delegation::get_delegation_pubkey(realm, delegator),
+                    false,
+                ),
+                AccountMeta::new(UserVotes::get_uservote_pubkey(delegator, vote), false),
+            ]);
+        }
+        instruction
+    }
+
+    pub fn delegate(delegator: &Pubkey, realm: &Pubkey, delegate: &Pubkey) -> Instruction {
+        Instruction::new_with_borsh(
+            id(),
+            &VoteInstruction::Delegate { delegate: *delegate },
             vec![
-                AccountMeta::new_readonly(*user, true),
-                AccountMeta::new(user_votes_pubkey, false),
-                AccountMeta::new(*vote, false),
+                AccountMeta::new(*delegator, true),
+                AccountMeta::new_readonly(*realm, false),
+                AccountMeta::new(Delegation::get_delegation_pubkey(realm, delegator), false),
                 AccountMeta::new_readonly(sysvar::rent::id(), false),
                 AccountMeta::new_readonly(system_program::id(), false),
             ],
         )
     }
 
-    pub fn create_vote_counter(admin: &Pubkey) -> Instruction {
-        let (vote_counter_pubkey, _) = VoteCounter::get_vote_pubkey_with_bump();
+    pub fn revoke_delegation(delegator: &Pubkey, realm: &Pubkey) -> Instruction {
         Instruction::new_with_borsh(
             id(),
-            &VoteInstruction::CreateVoteCounter,
+            &VoteInstruction::RevokeDelegation,
+            vec![
+                AccountMeta::new(*delegator, true),
+                AccountMeta::new_readonly(*realm, false),
+                AccountMeta::new(Delegation::get_delegation_pubkey(realm, delegator), false),
+            ],
+        )
+    }
+
+    pub fn vote_as_member(user: &Pubkey, vote: &Pubkey, direction: Direction) -> Instruction {
+        let membership_pubkey = Membership::get_membership_pubkey(vote, user);
+        let mut instruction = Self::vote(user, vote, direction);
+        instruction.accounts.push(AccountMeta::new_readonly(membership_pubkey, false));
+        instruction
+    }
+
+    pub fn add_voter(
+        authority: &Pubkey,
+        realm: &Pubkey,
+        vote: &Pubkey,
+        voter: &Pubkey,
+    ) -> Instruction {
+        Instruction::new_with_borsh(
+            id(),
+            &VoteInstruction::AddVoter { voter: *voter },
+            vec![
+                AccountMeta::new(*authority, true),
+                AccountMeta::new_readonly(*vote, false),
+                AccountMeta::new_readonly(*realm, false),
+                AccountMeta::new(Membership::get_membership_pubkey(vote, voter), false),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        )
+    }
+
+    pub fn remove_voter(
+        authority: &Pubkey,
+        realm: &Pubkey,
+        vote: &Pubkey,
+        voter: &Pubkey,
+    ) -> Instruction {
+        Instruction::new_with_borsh(
+            id(),
+            &VoteInstruction::RemoveVoter { voter: *voter },
+            vec![
+                AccountMeta::new(*authority, true),
+                AccountMeta::new_readonly(*vote, false),
+                AccountMeta::new_readonly(*realm, false),
+                AccountMeta::new(Membership::get_membership_pubkey(vote, voter), false),
+            ],
+        )
+    }
+
+    pub fn vote_with_proof(
+        user: &Pubkey,
+        vote: &Pubkey,
+        direction: Direction,
+        credits: Option<u64>,
+        proof: VoterProof,
+    ) -> Instruction {
+        Instruction::new_with_borsh(
+            id(),
+            &VoteInstruction::VoteWithProof { direction, credits, proof },
+            Self::vote_accounts(user, vote),
+        )
+    }
+
+    fn vote_accounts(user: &Pubkey, vote: &Pubkey) -> Vec<AccountMeta> {
+        let user_votes_pubkey = UserVotes::get_uservote_pubkey(user, vote);
+        vec![
+            AccountMeta::new(*user, true),
+            AccountMeta::new(user_votes_pubkey, false),
+            AccountMeta::new(*vote, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ]
+    }
+
+    pub fn vote_with_deposit(
+        user: &Pubkey,
+        vote: &Pubkey,
+        governing_mint: &Pubkey,
+        direction: Direction,
+    ) -> Instruction {
+        let voter_record_pubkey = VoterRecord::get_voter_record_pubkey(user, governing_mint);
+        let mut instruction = Self::vote(user, vote, direction);
+        instruction.accounts.push(AccountMeta::new(voter_record_pubkey, false));
+        instruction
+    }
+
+    pub fn create_realm(admin: &Pubkey, name: &str, settings: Settings) -> Instruction {
+        let (vote_counter_pubkey, _) = VoteCounter::get_vote_pubkey_with_bump(name);
+        Instruction::new_with_borsh(
+            id(),
+            &VoteInstruction::CreateRealm { name: name.to_string(), settings },
             vec![
                 AccountMeta::new(*admin, true),
                 AccountMeta::new(vote_counter_pubkey, false),
@@ -98,16 +594,392 @@ impl VoteInstruction {
         )
     }
 
-    pub fn create_vote(admin: &Pubkey, vote_seed: &Pubkey) -> Instruction {
-        let (vote_pubkey, _) = Vote::get_vote_pubkey_with_bump(vote_seed);
-        let (vote_counter_pubkey, _) = VoteCounter::get_vote_pubkey_with_bump();
+    pub fn update_settings(authority: &Pubkey, realm: &Pubkey, settings: Settings) -> Instruction {
+        Instruction::new_with_borsh(
+            id(),
+            &VoteInstruction::UpdateSettings { settings },
+            vec![AccountMeta::new_readonly(*authority, true), AccountMeta::new(*realm, false)],
+        )
+    }
+
+    pub fn finalize(cranker: &Pubkey, realm: &Pubkey, vote: &Pubkey) -> Instruction {
+        Instruction::new_with_borsh(
+            id(),
+            &VoteInstruction::Finalize,
+            vec![
+                AccountMeta::new_readonly(*cranker, true),
+                AccountMeta::new(*vote, false),
+                AccountMeta::new(*realm, false),
+                AccountMeta::new_readonly(sysvar::clock::id(), false),
+            ],
+        )
+    }
+
+    pub fn close_vote(admin: &Pubkey, vote: &Pubkey, recipient: &Pubkey) -> Instruction {
         Instruction::new_with_borsh(
             id(),
-            &VoteInstruction::CreateVote { vote_seed: *vote_seed },
+            &VoteInstruction::CloseVote,
+            vec![
+                AccountMeta::new_readonly(*admin, true),
+                AccountMeta::new(*vote, false),
+                AccountMeta::new(*recipient, false),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+            ],
+        )
+    }
+
+    pub fn close_user_vote(user: &Pubkey, vote: &Pubkey) -> Instruction {
+        let user_votes_pubkey = UserVotes::get_uservote_pubkey(user, vote);
+        Instruction::new_with_borsh(
+            id(),
+            &VoteInstruction::CloseUserVote,
+            vec![
+                AccountMeta::new(*user, true),
+                AccountMeta::new(user_votes_pubkey, false),
+                AccountMeta::new_readonly(*vote, false),
+            ],
+        )
+    }
+
+    pub fn close_user_vote_with_deposit(
+        user: &Pubkey,
+        vote: &Pubkey,
+        governing_mint: &Pubkey,
+    ) -> Instruction {
+        let voter_record_pubkey = VoterRecord::get_voter_record_pubkey(user, governing_mint);
+        let mut instruction = Self::close_user_vote(user, vote);
+        instruction.accounts.push(AccountMeta::new(voter_record_pubkey, false));
+        instruction
+    }
+
+    pub fn deposit(
+        voter: &Pubkey,
+        governing_mint: &Pubkey,
+        source: &Pubkey,
+        amount: u64,
+        lockup_secs: u64,
+    ) -> Instruction {
+        Instruction::new_with_borsh(
+            id(),
+            &VoteInstruction::Deposit { amount, lockup_secs },
+            vec![
+                AccountMeta::new(*voter, true),
+                AccountMeta::new(
+                    VoterRecord::get_voter_record_pubkey(voter, governing_mint),
+                    false,
+                ),
+                AccountMeta::new(VoterRecord::get_escrow_pubkey(voter, governing_mint), false),
+                AccountMeta::new(*source, false),
+                AccountMeta::new_readonly(*governing_mint, false),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new_readonly(sysvar::clock::id(), false),
+            ],
+        )
+    }
+
+    pub fn withdraw(
+        voter: &Pubkey,
+        governing_mint: &Pubkey,
+        destination: &Pubkey,
+        amount: u64,
+    ) -> Instruction {
+        Instruction::new_with_borsh(
+            id(),
+            &VoteInstruction::Withdraw { amount },
+            vec![
+                AccountMeta::new_readonly(*voter, true),
+                AccountMeta::new(
+                    VoterRecord::get_voter_record_pubkey(voter, governing_mint),
+                    false,
+                ),
+                AccountMeta::new(VoterRecord::get_escrow_pubkey(voter, governing_mint), false),
+                AccountMeta::new(*destination, false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new_readonly(sysvar::clock::id(), false),
+            ],
+        )
+    }
+
+    pub fn retract_vote(user: &Pubkey, vote: &Pubkey) -> Instruction {
+        let user_votes_pubkey = UserVotes::get_uservote_pubkey(user, vote);
+        Instruction::new_with_borsh(
+            id(),
+            &VoteInstruction::RetractVote,
+            vec![
+                AccountMeta::new_readonly(*user, true),
+                AccountMeta::new(user_votes_pubkey, false),
+                AccountMeta::new(*vote, false),
+                AccountMeta::new_readonly(sysvar::clock::id(), false),
+            ],
+        )
+    }
+
+    pub fn retract_vote_with_deposit(
+        user: &Pubkey,
+        vote: &Pubkey,
+        governing_mint: &Pubkey,
+    ) -> Instruction {
+        let voter_record_pubkey = VoterRecord::get_voter_record_pubkey(user, governing_mint);
+        let mut instruction = Self::retract_vote(user, vote);
+        instruction.accounts.push(AccountMeta::new(voter_record_pubkey, false));
+        instruction
+    }
+
+    pub fn vote_anonymous(
+        payer: &Pubkey,
+        vote: &Pubkey,
+        direction: Direction,
+        voter_key: &Pubkey,
+        path: Vec<[u8; 32]>,
+    ) -> Instruction {
+        let nullifier = anonymous_nullifier(voter_key, vote);
+        let mut accounts = Self::vote_anonymous_accounts(payer, vote, &nullifier);
+        accounts.push(AccountMeta::new_readonly(*voter_key, true));
+        Instruction::new_with_borsh(
+            id(),
+            &VoteInstruction::VoteAnonymous {
+                direction,
+                nullifier,
+                proof: AnonymousProof::Signer { path },
+            },
+            accounts,
+        )
+    }
+
+    pub fn vote_anonymous_with_verifier(
+        payer: &Pubkey,
+        vote: &Pubkey,
+        direction: Direction,
+        nullifier: [u8; 32],
+        verifier: &Pubkey,
+        proof: Vec<u8>,
+    ) -> Instruction {
+        let mut accounts = Self::vote_anonymous_accounts(payer, vote, &nullifier);
+        accounts.push(AccountMeta::new_readonly(*verifier, false));
+        Instruction::new_with_borsh(
+            id(),
+            &VoteInstruction::VoteAnonymous {
+                direction,
+                nullifier,
+                proof: AnonymousProof::Verifier { proof },
+            },
+            accounts,
+        )
+    }
+
+    fn vote_anonymous_accounts(
+        payer: &Pubkey,
+        vote: &Pubkey,
+        nullifier: &[u8; 32],
+    ) -> Vec<AccountMeta> {
+        let mut accounts = Self::vote_accounts(payer, vote);
+        accounts[1] = AccountMeta::new(UserVotes::get_nullifier_pubkey(nullifier, vote), false);
+        accounts
+    }
+
+    pub fn reveal(
+        user: &Pubkey,
+        vote: &Pubkey,
+        direction: Direction,
+        salt: [u8; 32],
+    ) -> Instruction {
+        let user_votes_pubkey = UserVotes::get_uservote_pubkey(user, vote);
+        Instruction::new_with_borsh(
+            id(),
+            &VoteInstruction::Reveal { direction, salt },
+            vec![
+                AccountMeta::new_readonly(*user, true),
+                AccountMeta::new(user_votes_pubkey, false),
+                AccountMeta::new(*vote, false),
+                AccountMeta::new_readonly(sysvar::clock::id(), false),
+            ],
+        )
+    }
+
+    pub fn relay_vote(
+        relayer: &Pubkey,
+        voter: &Pubkey,
+        vote: &Pubkey,
+        direction: Direction,
+        nonce: u64,
+    ) -> Instruction {
+        let mut accounts = Self::vote_accounts(relayer, vote);
+        accounts[1] = AccountMeta::new(UserVotes::get_uservote_pubkey(voter, vote), false);
+        accounts.push(AccountMeta::new_readonly(sysvar::instructions::id(), false));
+        Instruction::new_with_borsh(
+            id(),
+            &VoteInstruction::RelayVote { voter: *voter, direction, nonce },
+            accounts,
+        )
+    }
+
+    pub fn vote_many(user: &Pubkey, ballots: Vec<(Pubkey, Direction)>) -> Instruction {
+        let mut accounts = vec![
+            AccountMeta::new(*user, true),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ];
+        for (vote, _) in &ballots {
+            accounts.push(AccountMeta::new(UserVotes::get_uservote_pubkey(user, vote), false));
+            accounts.push(AccountMeta::new(*vote, false));
+        }
+        Instruction::new_with_borsh(id(), &VoteInstruction::VoteMany { ballots }, accounts)
+    }
+
+    /// Ed25519 program instruction verifying `signature` of `voter` over `message`,
+    /// must directly precede `relay_vote`
+    pub fn verify_relay_signature(
+        voter: &Pubkey,
+        signature: &[u8; 64],
+        message: &[u8],
+    ) -> Instruction {
+        const HEADER_LEN: u16 = 16;
+        let public_key_offset = HEADER_LEN;
+        let signature_offset = public_key_offset + 32;
+        let message_offset = signature_offset + 64;
+
+        let mut data = vec![1, 0];
+        for offset in [
+            signature_offset,
+            u16::MAX,
+            public_key_offset,
+            u16::MAX,
+            message_offset,
+            message.len() as u16,
+            u16::MAX,
+        ] {
+            data.extend_from_slice(&offset.to_le_bytes());
+        }
+        data.extend_from_slice(voter.as_ref());
+        data.extend_from_slice(signature);
+        data.extend_from_slice(message);
+
+        Instruction { program_id: ed25519_program::id(), accounts: vec![], data }
+    }
+
+    pub fn add_proposal_transaction(
+        admin: &Pubkey,
+        authority: &Pubkey,
+        realm: &Pubkey,
+        vote: &Pubkey,
+        index: u16,
+        instruction: ProposalInstruction,
+    ) -> Instruction {
+        Instruction::new_with_borsh(
+            id(),
+            &VoteInstruction::AddProposalTransaction { index, instruction },
+            vec![
+                AccountMeta::new(*admin, true),
+                AccountMeta::new_readonly(*authority, true),
+                AccountMeta::new_readonly(*realm, false),
+                AccountMeta::new(*vote, false),
+                AccountMeta::new(
+                    ProposalTransaction::get_proposal_transaction_pubkey(vote, index),
+                    false,
+                ),
+                AccountMeta::new_readonly(sysvar::rent::id(), false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        )
+    }
+
+    pub fn execute(
+        realm: &Pubkey,
+        vote: &Pubkey,
+        index: u16,
+        instruction: &ProposalInstruction,
+    ) -> Instruction {
+        let governance_pubkey = VoteCounter::get_governance_pubkey(realm);
+        let mut accounts = vec![
+            AccountMeta::new_readonly(*vote, false),
+            AccountMeta::new_readonly(governance_pubkey, false),
+            AccountMeta::new(
+                ProposalTransaction::get_proposal_transaction_pubkey(vote, index),
+                false,
+            ),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(instruction.program_id, false),
+        ];
+        for account in &instruction.accounts {
+            accounts.push(AccountMeta {
+                pubkey: account.pubkey,
+                is_signer: account.is_signer && account.pubkey != governance_pubkey,
+                is_writable: account.is_writable,
+            });
+        }
+        Instruction::new_with_borsh(id(), &VoteInstruction::Execute, accounts)
+    }
+
+    pub fn close_proposal_transaction(
+        authority: &Pubkey,
+        realm: &Pubkey,
+        vote: &Pubkey,
+        index: u16,
+    ) -> Instruction {
+        Instruction::new_with_borsh(
+            id(),
+            &VoteInstruction::CloseProposalTransaction,
+            vec![
+                AccountMeta::new(*authority, true),
+                AccountMeta::new_readonly(*realm, false),
+                AccountMeta::new(*vote, false),
+                AccountMeta::new(
+                    ProposalTransaction::get_proposal_transaction_pubkey(vote, index),
+                    false,
+                ),
+                AccountMeta::new_readonly(sysvar::clock::id(), false),
+            ],
+        )
+    }
+
+    pub fn close_nullifier(payer: &Pubkey, vote: &Pubkey, nullifier: [u8; 32]) -> Instruction {
+        Instruction::new_with_borsh(
+            id(),
+            &VoteInstruction::CloseNullifier { nullifier },
+            vec![
+                AccountMeta::new(*payer, true),
+                AccountMeta::new(UserVotes::get_nullifier_pubkey(&nullifier, vote), false),
+                AccountMeta::new_readonly(*vote, false),
+            ],
+        )
+    }
+
+    pub fn tally(vote: &Pubkey, voters: &[Pubkey]) -> Instruction {
+        let mut accounts = vec![AccountMeta::new(*vote, false)];
+        for voter in voters {
+            accounts.push(AccountMeta::new_readonly(*voter, false));
+            accounts.push(AccountMeta::new(UserVotes::get_uservote_pubkey(voter, vote), false));
+        }
+        Instruction::new_with_borsh(id(), &VoteInstruction::Tally, accounts)
+    }
+
+    pub fn tally_anonymous(vote: &Pubkey, nullifiers: &[[u8; 32]]) -> Instruction {
+        let mut accounts = vec![AccountMeta::new(*vote, false)];
+        for nullifier in nullifiers {
+            accounts.push(AccountMeta::new_readonly(Pubkey::new_from_array(*nullifier), false));
+            accounts
+                .push(AccountMeta::new(UserVotes::get_nullifier_pubkey(nullifier, vote), false));
+        }
+        Instruction::new_with_borsh(id(), &VoteInstruction::Tally, accounts)
+    }
+
+    pub fn create_vote(
+        admin: &Pubkey,
+        realm: &Pubkey,
+        vote_seed: &Pubkey,
+        config: VoteConfig,
+    ) -> Instruction {
+        let (vote_pubkey, _) = Vote::get_vote_pubkey_with_bump(realm, vote_seed);
+        Instruction::new_with_borsh(
+            id(),
+            &VoteInstruction::CreateVote { vote_seed: *vote_seed, config },
             vec![
                 AccountMeta::new(*admin, true),
                 AccountMeta::new(vote_pubkey, false),
-                AccountMeta::new(vote_counter_pubkey, false),
+                AccountMeta::new(*realm, false),
                 AccountMeta::new_readonly(sysvar::rent::id(), false),
                 AccountMeta::new_readonly(system_program::id(), false),
                 AccountMeta::new_readonly(sysvar::clock::id(), false),
@@ -1,5 +1,6 @@
 pub mod error;
 pub mod instruction;
+pub mod metadata;
 pub mod processor;
 pub mod state;
 
@@ -8,4 +9,11 @@ pub mod entrypoint;
 
 pub const VOTE_SEED: &str = "vote";
 pub const SETTINGS_SEED: &str = "settings";
+pub const REGISTRY_SEED: &str = "registry";
+pub const ESCROW_SEED: &str = "escrow";
+pub const MEMBER_SEED: &str = "member";
+pub const DELEGATION_SEED: &str = "delegation";
+pub const GOVERNANCE_SEED: &str = "governance";
+pub const PROPOSAL_SEED: &str = "proposal";
+pub const NULLIFIER_SEED: &str = "nullifier";
 solana_program::declare_id!("78yZvMzqAFzSHJrLNVWfqLRFFQ5ZCGzNXB4PBxmp6z5Y");
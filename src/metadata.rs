@@ -0,0 +1,66 @@
+//! Minimal reader of Metaplex token metadata accounts, only the fields needed to check
+//! collection of NFT are decoded, the rest of account is ignored.
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
+
+pub mod metadata_program {
+    solana_program::declare_id!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+}
+
+pub const METADATA_SEED: &str = "metadata";
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct Creator {
+    pub address: Pubkey,
+    pub verified: bool,
+    pub share: u8,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct Data {
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+    pub seller_fee_basis_points: u16,
+    pub creators: Option<Vec<Creator>>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct Collection {
+    /// Set by collection authority, unverified collection can be claimed by anyone
+    pub verified: bool,
+    pub key: Pubkey,
+}
+
+/// Leading fields of metadata account, in layout of Metaplex program
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct Metadata {
+    pub key: u8,
+    pub update_authority: Pubkey,
+    pub mint: Pubkey,
+    pub data: Data,
+    pub primary_sale_happened: bool,
+    pub is_mutable: bool,
+    pub edition_nonce: Option<u8>,
+    pub token_standard: Option<u8>,
+    pub collection: Option<Collection>,
+}
+
+impl Metadata {
+    /// Decode leading fields, account may be longer
+    pub fn from_account_data(data: &[u8]) -> Option<Self> {
+        Self::deserialize(&mut &data[..]).ok()
+    }
+
+    pub fn get_metadata_pubkey(mint: &Pubkey) -> Pubkey {
+        let (pubkey, _) = Pubkey::find_program_address(
+            &[METADATA_SEED.as_bytes(), &metadata_program::id().to_bytes(), &mint.to_bytes()],
+            &metadata_program::id(),
+        );
+        pubkey
+    }
+
+    pub fn is_verified_in(&self, collection: &Pubkey) -> bool {
+        matches!(&self.collection, Some(Collection { verified: true, key }) if key == collection)
+    }
+}
@@ -1,58 +1,314 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::account_info::{next_account_info, AccountInfo};
 use solana_program::entrypoint::ProgramResult;
-use solana_program::program::invoke_signed;
+use solana_program::hash::hashv;
+use solana_program::instruction::Instruction;
+use solana_program::program::{invoke, invoke_signed};
+use solana_program::program_error::ProgramError;
+use solana_program::program_pack::Pack;
 use solana_program::pubkey::Pubkey;
-use solana_program::sysvar::{clock::Clock, rent::Rent, Sysvar};
-use solana_program::{msg, system_instruction};
+use solana_program::sysvar::{clock::Clock, instructions, rent::Rent, Sysvar};
+use solana_program::{ed25519_program, msg, system_instruction};
 
-use crate::error::VoteError;
-use crate::instruction::{Direction, VoteInstruction};
-use crate::state::{UserVotes, Vote, VoteCounter, VoteStatus};
-use crate::{id, SETTINGS_SEED, VOTE_SEED};
+use crate::error::{VoteError, BATCH_ENTRY_ERROR};
+use crate::instruction::{
+    AnonymousProof, AnonymousStatement, Direction, VoteInstruction, VoterProof,
+};
+use crate::metadata::{metadata_program, Metadata};
+use crate::state::{
+    allowlist_node, anonymous_leaf, anonymous_nullifier, BallotKind, Delegation, Membership,
+    ProposalInstruction, ProposalTransaction, Settings, UserVotes, Vote, VoteConfig, VoteCounter,
+    VoteStatus, VoterRecord, VotingMode, MAX_LOCKUP_SECS, MAX_THRESHOLD, NO_WINNER,
+};
+use crate::{
+    id, DELEGATION_SEED, ESCROW_SEED, GOVERNANCE_SEED, MEMBER_SEED, NULLIFIER_SEED, PROPOSAL_SEED,
+    REGISTRY_SEED, SETTINGS_SEED, VOTE_SEED,
+};
 
 pub struct Processor;
 
-pub const MAX_VOTES: u8 = 10;
-pub const TIME_TO_LIVE: u64 = 10;
-
 impl Processor {
     pub fn process(_program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
         let instruction = VoteInstruction::try_from_slice(input)?;
         match instruction {
-            VoteInstruction::Vote { direction } => Self::process_vote(direction, accounts),
-            VoteInstruction::CreateVote { vote_seed } => Self::process_create(accounts, vote_seed),
+            VoteInstruction::Vote { direction } => {
+                Self::process_vote(direction, None, None, None, accounts)
+            }
+            VoteInstruction::VoteWithCredits { direction, credits } => {
+                Self::process_vote(direction, Some(credits), None, None, accounts)
+            }
+            VoteInstruction::VoteWithProof { direction, credits, proof } => {
+                Self::process_vote(direction, credits, Some(proof), None, accounts)
+            }
+            VoteInstruction::CreateVote { vote_seed, config } => {
+                Self::process_create(accounts, vote_seed, config)
+            }
             VoteInstruction::DeleteVote { admin } => Self::process_delete(accounts, admin),
-            VoteInstruction::CreateVoteCounter => Self::process_create_counter(accounts),
+            VoteInstruction::CreateRealm { name, settings } => {
+                Self::process_create_realm(accounts, name, settings)
+            }
+            VoteInstruction::UpdateSettings { settings } => {
+                Self::process_update_settings(accounts, settings)
+            }
+            VoteInstruction::CloseVote => Self::process_close_vote(accounts),
+            VoteInstruction::CloseUserVote => Self::process_close_user_vote(accounts),
+            VoteInstruction::Finalize => Self::process_finalize(accounts),
+            VoteInstruction::Deposit { amount, lockup_secs } => {
+                Self::process_deposit(accounts, amount, lockup_secs)
+            }
+            VoteInstruction::Withdraw { amount } => Self::process_withdraw(accounts, amount),
+            VoteInstruction::Tally => Self::process_tally(accounts),
+            VoteInstruction::RetractVote => Self::process_retract_vote(accounts),
+            VoteInstruction::AddVoter { voter } => Self::process_add_voter(accounts, voter),
+            VoteInstruction::RemoveVoter { voter } => Self::process_remove_voter(accounts, voter),
+            VoteInstruction::Delegate { delegate } => Self::process_delegate(accounts, delegate),
+            VoteInstruction::RevokeDelegation => Self::process_revoke_delegation(accounts),
+            VoteInstruction::Reveal { direction, salt } => {
+                Self::process_reveal(accounts, direction, salt)
+            }
+            VoteInstruction::VoteAnonymous { direction, nullifier, proof } => {
+                Self::process_vote_anonymous(accounts, direction, nullifier, proof)
+            }
+            VoteInstruction::RelayVote { voter, direction, nonce } => {
+                Self::process_vote(direction, None, None, Some((voter, nonce)), accounts)
+            }
+            VoteInstruction::VoteMany { ballots } => Self::process_vote_many(accounts, ballots),
+            VoteInstruction::AddProposalTransaction { index, instruction } => {
+                Self::process_add_proposal_transaction(accounts, index, instruction)
+            }
+            VoteInstruction::Execute => Self::process_execute(accounts),
+            VoteInstruction::CloseProposalTransaction => {
+                Self::process_close_proposal_transaction(accounts)
+            }
+            VoteInstruction::CloseNullifier { nullifier } => {
+                Self::process_close_nullifier(accounts, nullifier)
+            }
         }
     }
 
-    fn process_vote(direction: Direction, accounts: &[AccountInfo]) -> ProgramResult {
+    fn process_vote(
+        direction: Direction,
+        credits: Option<u64>,
+        proof: Option<VoterProof>,
+        relay: Option<(Pubkey, u64)>,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
         let acc_iter = &mut accounts.iter();
         let user_info = next_account_info(acc_iter)?;
         let participate_info = next_account_info(acc_iter)?;
         let vote_info = next_account_info(acc_iter)?;
         let rent_info = next_account_info(acc_iter)?;
         let system_program_info = next_account_info(acc_iter)?;
+        let clock_sysvar_info = next_account_info(acc_iter)?;
 
         if !user_info.is_signer {
             return Err(VoteError::SignedRequired.into());
         }
 
+        if vote_info.owner != &id() {
+            return Err(VoteError::WrongVoteDefine.into());
+        }
+
+        let mut vote = Vote::try_from_slice(&vote_info.data.borrow())?;
+
+        // relayed ballot is paid by relayer and authenticated by voter signature checked
+        // by Ed25519 program in preceding instruction
+        let voter = match relay {
+            Some((voter, nonce)) => {
+                let instructions_info = next_account_info(acc_iter)?;
+                let message = direction.relay_message(vote_info.key, nonce);
+                Self::check_relay_signature(instructions_info, &voter, &message)?;
+                voter
+            }
+            None => *user_info.key,
+        };
+
+        if vote.config.anonymous_root.is_some() {
+            return Err(VoteError::WrongVotingMode.into());
+        }
+
+        // ballot of collection-gated vote belongs to NFT, so it is cast once whoever holds it
+        let ballot_key = match vote.config.collection {
+            Some(collection) => {
+                let token_info = next_account_info(acc_iter)?;
+                let metadata_info = next_account_info(acc_iter)?;
+                let mint = Self::nft_mint(&voter, token_info)?;
+                Self::check_collection(metadata_info, &mint, &collection)?;
+                mint
+            }
+            None => voter,
+        };
+
+        Self::create_user_votes(
+            user_info,
+            participate_info,
+            &ballot_key,
+            vote_info,
+            rent_info,
+            system_program_info,
+        )?;
+
+        let mut participation = UserVotes::try_from_slice(&participate_info.data.borrow())?;
+
+        if let Some((_, nonce)) = relay {
+            if nonce <= participation.relay_nonce {
+                return Err(VoteError::StaleNonce.into());
+            }
+
+            participation.relay_nonce = nonce;
+        }
+
+        if vote.status != VoteStatus::Alive {
+            return Err(VoteError::CloseVoteParticipate.into());
+        }
+
+        let clock = Clock::from_account_info(clock_sysvar_info)?;
+
+        if !vote.is_started(clock.unix_timestamp) {
+            return Err(VoteError::VoteNotStarted.into());
+        }
+
+        if vote.is_ended(clock.unix_timestamp) {
+            return Err(VoteError::VoteEnded.into());
+        }
+
+        let allowlist_weight = match vote.config.voter_root {
+            Some(root) => {
+                let proof = proof.ok_or(VoteError::NotAllowlisted)?;
+
+                if !proof.verify(&root, &voter) {
+                    return Err(VoteError::NotAllowlisted.into());
+                }
+
+                proof.weight
+            }
+            None => 1,
+        };
+
+        let weight = Self::voter_weight(
+            &vote,
+            &mut participation,
+            &voter,
+            acc_iter,
+            &clock,
+            allowlist_weight,
+        )?;
+
+        if vote.config.members_only {
+            let membership_info = next_account_info(acc_iter)?;
+            Self::load_membership(membership_info, vote_info.key, &voter)?;
+        }
+
+        Self::cast_ballot(&mut vote, &mut participation, &direction, weight, credits)?;
+        participation.cast_by = [0; 32];
+
+        // remaining accounts are delegators the voter casts the same ballot for
+        while let Some(delegator_info) = acc_iter.next() {
+            if credits.is_some()
+                || vote.config.voter_root.is_some()
+                || vote.config.collection.is_some()
+                || vote.config.members_only
+                || vote.is_commit_reveal()
+            {
+                return Err(VoteError::DelegationNotAllowed.into());
+            }
+
+            let delegation_info = next_account_info(acc_iter)?;
+            let delegated_info = next_account_info(acc_iter)?;
+            let realm = Pubkey::new_from_array(vote.realm);
+            Self::load_delegation(delegation_info, &realm, delegator_info.key, &voter)?;
+            Self::create_user_votes(
+                user_info,
+                delegated_info,
+                delegator_info.key,
+                vote_info,
+                rent_info,
+                system_program_info,
+            )?;
+
+            let mut delegated = UserVotes::try_from_slice(&delegated_info.data.borrow())?;
+
+            // ballot cast by delegator personally takes precedence over delegate
+            if delegated.is_voted() && delegated.cast_by == [0; 32] {
+                continue;
+            }
+
+            let weight =
+                Self::voter_weight(&vote, &mut delegated, delegator_info.key, acc_iter, &clock, 1)?;
+            Self::cast_ballot(&mut vote, &mut delegated, &direction, weight, None)?;
+            delegated.cast_by = voter.to_bytes();
+
+            let _ = delegated.serialize(&mut &mut delegated_info.data.borrow_mut()[..]);
+        }
+
+        let _ = participation.serialize(&mut &mut participate_info.data.borrow_mut()[..]);
+        let _ = vote.serialize(&mut &mut vote_info.data.borrow_mut()[..]);
+
+        Ok(())
+    }
+
+    /// Cast every ballot of batch as separate `Vote`, first failed ballot fails whole batch
+    fn process_vote_many(
+        accounts: &[AccountInfo],
+        ballots: Vec<(Pubkey, Direction)>,
+    ) -> ProgramResult {
+        let acc_iter = &mut accounts.iter();
+        let user_info = next_account_info(acc_iter)?;
+        let rent_info = next_account_info(acc_iter)?;
+        let system_program_info = next_account_info(acc_iter)?;
+        let clock_sysvar_info = next_account_info(acc_iter)?;
+
+        for (index, (vote, direction)) in ballots.into_iter().enumerate() {
+            let result = next_account_info(acc_iter).and_then(|participate_info| {
+                let vote_info = next_account_info(acc_iter)?;
+
+                if *vote_info.key != vote {
+                    return Err(VoteError::WrongVoteDefine.into());
+                }
+
+                let entry_accounts = [
+                    user_info.clone(),
+                    participate_info.clone(),
+                    vote_info.clone(),
+                    rent_info.clone(),
+                    system_program_info.clone(),
+                    clock_sysvar_info.clone(),
+                ];
+                Self::process_vote(direction, None, None, None, &entry_accounts)
+            });
+
+            if let Err(err) = result {
+                msg!("VoteMany: ballot {} for vote {} failed: {:?}", index, vote, err);
+                return Err(ProgramError::Custom(BATCH_ENTRY_ERROR + index as u32));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check `UserVotes` PDA of `ballot_key` and create it on first ballot, paid by user
+    fn create_user_votes<'a>(
+        user_info: &AccountInfo<'a>,
+        participate_info: &AccountInfo<'a>,
+        ballot_key: &Pubkey,
+        vote_info: &AccountInfo<'a>,
+        rent_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+    ) -> ProgramResult {
         let (participate_pubkey, bump_seed) =
-            UserVotes::get_uservote_pubkey_with_bump(user_info.key, vote_info.key);
+            UserVotes::get_uservote_pubkey_with_bump(ballot_key, vote_info.key);
 
         if participate_pubkey != *participate_info.key {
             return Err(VoteError::WrongUserVotePDA.into());
         }
 
         if participate_info.data_is_empty() {
-            let participate = UserVotes { is_voted: false };
+            let participate = UserVotes::default();
             let space = participate.try_to_vec()?.len();
             let rent = &Rent::from_account_info(rent_info)?;
             let lamports = rent.minimum_balance(space);
             let signer_seeds: &[&[_]] = &[
-                &user_info.key.to_bytes(),
+                &ballot_key.to_bytes(),
                 &vote_info.key.to_bytes(),
                 VOTE_SEED.as_bytes(),
                 &[bump_seed],
@@ -71,31 +327,274 @@ impl Processor {
             let _ = participate.serialize(&mut &mut participate_info.data.borrow_mut()[..]);
         }
 
-        let mut participation = UserVotes::try_from_slice(&participate_info.data.borrow())?;
-        let mut vote = Vote::try_from_slice(&vote_info.data.borrow())?;
+        Ok(())
+    }
 
-        if participation.is_voted {
-            return Err(VoteError::DoubleParticipate.into());
+    /// Voting power of voter record taken as next account if vote is token-weighted,
+    /// `default_weight` otherwise
+    fn voter_weight<'a>(
+        vote: &Vote,
+        participation: &mut UserVotes,
+        voter: &Pubkey,
+        acc_iter: &mut std::slice::Iter<AccountInfo<'a>>,
+        clock: &Clock,
+        default_weight: u64,
+    ) -> Result<u64, ProgramError> {
+        let mint = match vote.config.governing_mint {
+            Some(mint) => mint,
+            None => return Ok(default_weight),
+        };
+
+        let voter_record_info = next_account_info(acc_iter)?;
+        let mut voter_record = Self::load_voter_record(voter_record_info, voter, &mint)?;
+
+        if voter_record.amount == 0 {
+            return Err(VoteError::NoVotingPower.into());
         }
 
-        if vote.status != VoteStatus::Alive {
-            return Err(VoteError::CloseVoteParticipate.into());
+        if participation.governing_mint == [0; 32] {
+            voter_record.active_votes += 1;
+            participation.governing_mint = mint.to_bytes();
+        }
+        let _ = voter_record.serialize(&mut &mut voter_record_info.data.borrow_mut()[..]);
+
+        Ok(voter_record.voting_power(clock.unix_timestamp))
+    }
+
+    /// Replace ballot of `participation` with `direction` counted with `weight`
+    fn cast_ballot(
+        vote: &mut Vote,
+        participation: &mut UserVotes,
+        direction: &Direction,
+        weight: u64,
+        credits: Option<u64>,
+    ) -> ProgramResult {
+        if matches!(direction, Direction::Commit(_)) != vote.is_commit_reveal() {
+            return Err(VoteError::WrongVotingMode.into());
         }
 
-        participation.is_voted = true;
+        Self::remove_ballot(vote, participation)?;
+
+        let votes = match (vote.config.mode, credits) {
+            (
+                VotingMode::Simple | VotingMode::Ranked | VotingMode::Approval | VotingMode::Score,
+                None,
+            ) => weight,
+            (VotingMode::Quadratic, Some(credits)) => {
+                let budget = weight
+                    .checked_mul(vote.config.voice_credits)
+                    .ok_or(VoteError::TallyOverflow)?;
+
+                if credits > budget {
+                    return Err(VoteError::CreditBudgetExceeded.into());
+                }
+
+                participation.credits_spent = credits;
+                isqrt(credits)
+            }
+            _ => return Err(VoteError::WrongVotingMode.into()),
+        };
+
+        Self::add_ballot(vote, participation, direction, votes)
+    }
+
+    fn add_ballot(
+        vote: &mut Vote,
+        participation: &mut UserVotes,
+        direction: &Direction,
+        votes: u64,
+    ) -> ProgramResult {
+        participation.ballot = direction.ballot_kind();
+        participation.weight = votes;
 
         match direction {
-            Direction::For => vote.all_votes_for += 1,
-            Direction::Against => vote.all_votes_against += 1,
+            Direction::Commit(commitment) => {
+                participation.commitment = *commitment;
+                vote.unrevealed += 1;
+                Ok(())
+            }
+            Direction::Ranking(ranking) => Self::add_ranking(vote, participation, ranking, votes),
+            Direction::Approve(approvals) => {
+                Self::add_approvals(vote, participation, *approvals, votes)
+            }
+            Direction::Scores(scores) => Self::add_scores(vote, participation, scores, votes),
+            Direction::Choice(index) => {
+                participation.choice = *index;
+                Self::add_votes(vote, direction, votes)
+            }
+            _ => Self::add_votes(vote, direction, votes),
+        }
+    }
+
+    /// Take back votes of ballot previously cast by user, so it can be changed or retracted
+    fn remove_ballot(vote: &mut Vote, participation: &mut UserVotes) -> ProgramResult {
+        let votes = participation.weight;
+        match participation.ballot {
+            BallotKind::None => {}
+            BallotKind::Commit => take_votes(&mut vote.unrevealed, 1)?,
+            BallotKind::For => take_votes(&mut vote.all_votes_for, votes)?,
+            BallotKind::Against => take_votes(&mut vote.all_votes_against, votes)?,
+            BallotKind::Abstain => take_votes(&mut vote.all_votes_abstain, votes)?,
+            BallotKind::Choice => {
+                let tally = vote
+                    .option_votes
+                    .get_mut(participation.choice as usize)
+                    .ok_or(VoteError::WrongOptionIndex)?;
+                take_votes(tally, votes)?
+            }
+            BallotKind::Ranking => {
+                let tally = vote.ranked_tally.as_mut().ok_or(VoteError::WrongVotingMode)?;
+                take_votes(&mut tally.ballots, 1)?;
+                let first = vote
+                    .option_votes
+                    .get_mut(participation.ranking[0] as usize)
+                    .ok_or(VoteError::WrongOptionIndex)?;
+                take_votes(first, votes)?;
+            }
+            BallotKind::Approve => {
+                for (option, tally) in vote.option_votes.iter_mut().enumerate() {
+                    if participation.approvals & (1 << option) != 0 {
+                        take_votes(tally, votes)?;
+                    }
+                }
+                take_votes(&mut vote.all_ballots, votes)?;
+            }
+            BallotKind::Scores => {
+                for (tally, &score) in vote.option_votes.iter_mut().zip(&participation.scores) {
+                    take_votes(tally, votes * score as u64)?;
+                }
+                take_votes(&mut vote.all_ballots, votes)?;
+            }
         }
 
-        let _ = participation.serialize(&mut &mut participate_info.data.borrow_mut()[..]);
-        let _ = vote.serialize(&mut &mut vote_info.data.borrow_mut()[..]);
+        participation.clear_ballot();
+
+        Ok(())
+    }
+
+    fn add_votes(vote: &mut Vote, direction: &Direction, votes: u64) -> ProgramResult {
+        let tally = match direction {
+            Direction::Ranking(_)
+            | Direction::Approve(_)
+            | Direction::Scores(_)
+            | Direction::Commit(_) => {
+                return Err(VoteError::WrongVotingMode.into());
+            }
+            Direction::For | Direction::Against | Direction::Choice(_)
+                if matches!(
+                    vote.config.mode,
+                    VotingMode::Ranked | VotingMode::Approval | VotingMode::Score
+                ) =>
+            {
+                return Err(VoteError::WrongVotingMode.into());
+            }
+            Direction::For | Direction::Against if vote.is_poll() => {
+                return Err(VoteError::WrongOptionIndex.into());
+            }
+            Direction::For => &mut vote.all_votes_for,
+            Direction::Against => &mut vote.all_votes_against,
+            Direction::Abstain => &mut vote.all_votes_abstain,
+            Direction::Choice(index) => {
+                vote.option_votes.get_mut(*index as usize).ok_or(VoteError::WrongOptionIndex)?
+            }
+        };
+
+        *tally = tally.checked_add(votes).ok_or(VoteError::TallyOverflow)?;
+
+        Ok(())
+    }
+
+    /// Store ranked ballot for `Tally`, first preference is counted in `option_votes`
+    fn add_ranking(
+        vote: &mut Vote,
+        participation: &mut UserVotes,
+        ranking: &[u8],
+        votes: u64,
+    ) -> ProgramResult {
+        let tally = vote.ranked_tally.as_mut().ok_or(VoteError::WrongVotingMode)?;
+        let options = vote.option_votes.len();
+
+        if ranking.is_empty() || ranking.len() > options {
+            return Err(VoteError::WrongRanking.into());
+        }
+
+        let mut ranked: u16 = 0;
+        for (place, &option) in ranking.iter().enumerate() {
+            if option as usize >= options || ranked & (1 << option) != 0 {
+                return Err(VoteError::WrongRanking.into());
+            }
+            ranked |= 1 << option;
+            participation.ranking[place] = option;
+        }
+
+        tally.ballots += 1;
+        let first = &mut vote.option_votes[ranking[0] as usize];
+        *first = first.checked_add(votes).ok_or(VoteError::TallyOverflow)?;
+
+        Ok(())
+    }
+
+    /// Count ballot once for every approved option and once toward participation
+    fn add_approvals(
+        vote: &mut Vote,
+        participation: &mut UserVotes,
+        approvals: u16,
+        votes: u64,
+    ) -> ProgramResult {
+        if vote.config.mode != VotingMode::Approval {
+            return Err(VoteError::WrongVotingMode.into());
+        }
+
+        let options = vote.option_votes.len();
+        if approvals == 0 || (approvals as u32) >> options != 0 {
+            return Err(VoteError::WrongApprovals.into());
+        }
+
+        for (option, tally) in vote.option_votes.iter_mut().enumerate() {
+            if approvals & (1 << option) != 0 {
+                *tally = tally.checked_add(votes).ok_or(VoteError::TallyOverflow)?;
+            }
+        }
+
+        vote.all_ballots = vote.all_ballots.checked_add(votes).ok_or(VoteError::TallyOverflow)?;
+        participation.approvals = approvals;
 
         Ok(())
     }
 
-    fn process_create(accounts: &[AccountInfo], vote_seed: Pubkey) -> ProgramResult {
+    /// Add weighted score of every option and count ballot once toward participation
+    fn add_scores(
+        vote: &mut Vote,
+        participation: &mut UserVotes,
+        scores: &[u8],
+        votes: u64,
+    ) -> ProgramResult {
+        if vote.config.mode != VotingMode::Score {
+            return Err(VoteError::WrongVotingMode.into());
+        }
+
+        if scores.len() != vote.option_votes.len()
+            || scores.iter().any(|&score| score > vote.config.max_score)
+        {
+            return Err(VoteError::WrongScores.into());
+        }
+
+        for (tally, &score) in vote.option_votes.iter_mut().zip(scores) {
+            let points = votes.checked_mul(score as u64).ok_or(VoteError::TallyOverflow)?;
+            *tally = tally.checked_add(points).ok_or(VoteError::TallyOverflow)?;
+        }
+
+        vote.all_ballots = vote.all_ballots.checked_add(votes).ok_or(VoteError::TallyOverflow)?;
+        participation.scores[..scores.len()].copy_from_slice(scores);
+
+        Ok(())
+    }
+
+    fn process_create(
+        accounts: &[AccountInfo],
+        vote_seed: Pubkey,
+        config: VoteConfig,
+    ) -> ProgramResult {
         let acc_iter = &mut accounts.iter();
         let admin_info = next_account_info(acc_iter)?;
         let vote_info = next_account_info(acc_iter)?;
@@ -104,7 +603,8 @@ impl Processor {
         let system_program_info = next_account_info(acc_iter)?;
         let clock_sysvar_info = next_account_info(acc_iter)?;
 
-        let (vote_pubkey, bump_seed) = Vote::get_vote_pubkey_with_bump(&vote_seed);
+        let (vote_pubkey, bump_seed) =
+            Vote::get_vote_pubkey_with_bump(vote_counter_info.key, &vote_seed);
 
         if vote_pubkey != *vote_info.key {
             return Err(VoteError::WrongVoteDefine.into());
@@ -114,18 +614,63 @@ impl Processor {
             return Err(VoteError::AdminRequired.into());
         }
 
-        let mut vote_counter = VoteCounter::try_from_slice(&vote_counter_info.data.borrow())?;
-        let time = Clock::from_account_info(clock_sysvar_info)?.slot;
+        let mut vote_counter = Self::load_vote_counter(vote_counter_info)?;
+        let clock = Clock::from_account_info(clock_sysvar_info)?;
 
-        if vote_counter.counter >= MAX_VOTES {
+        if vote_counter.counter >= vote_counter.settings.max_votes {
             return Err(VoteError::MaxVote.into());
         }
 
-        let vote = Vote::new(admin_info.key.to_bytes(), time);
+        if !config.is_valid() {
+            return Err(VoteError::WrongPollOptions.into());
+        }
+
+        if config.threshold > MAX_THRESHOLD {
+            return Err(VoteError::WrongThreshold.into());
+        }
+
+        if config.collection.is_some() && config.governing_mint.is_some() {
+            return Err(VoteError::WrongGating.into());
+        }
+
+        // anonymous ballot has no voter to weigh or gate, only its nullifier
+        let is_anonymous = config.anonymous_root.is_some();
+        if (is_anonymous
+            && (config.governing_mint.is_some()
+                || config.collection.is_some()
+                || config.voter_root.is_some()
+                || config.members_only
+                || config.reveal_end_ts.is_some()
+                || config.mode == VotingMode::Quadratic))
+            || (!is_anonymous && config.anonymous_verifier.is_some())
+        {
+            return Err(VoteError::WrongGating.into());
+        }
+
+        if let (Some(start_ts), Some(end_ts)) = (config.start_ts, config.end_ts) {
+            if start_ts >= end_ts {
+                return Err(VoteError::WrongVoteWindow.into());
+            }
+        }
+
+        if let Some(reveal_end_ts) = config.reveal_end_ts {
+            if !matches!(config.end_ts, Some(end_ts) if end_ts < reveal_end_ts) {
+                return Err(VoteError::WrongVoteWindow.into());
+            }
+        }
+
+        let vote = Vote::new(
+            admin_info.key.to_bytes(),
+            vote_counter_info.key.to_bytes(),
+            clock.slot,
+            clock.unix_timestamp,
+            config,
+        );
         let space = vote.try_to_vec()?.len();
         let rent = &Rent::from_account_info(rent_info)?;
         let lamports = rent.minimum_balance(space);
-        let signer_seeds: &[&[_]] = &[&vote_seed.to_bytes(), &[bump_seed]];
+        let signer_seeds: &[&[_]] =
+            &[&vote_counter_info.key.to_bytes(), &vote_seed.to_bytes(), &[bump_seed]];
         invoke_signed(
             &system_instruction::create_account(
                 admin_info.key,
@@ -161,17 +706,30 @@ impl Processor {
             return Err(VoteError::AdminRequired.into());
         }
 
+        if vote_info.owner != &id() {
+            return Err(VoteError::WrongVoteDefine.into());
+        }
+
         let mut vote = Vote::try_from_slice(&vote_info.data.borrow())?;
-        let mut vote_counter = VoteCounter::try_from_slice(&vote_counter_info.data.borrow())?;
+        let mut vote_counter = Self::load_vote_counter(vote_counter_info)?;
         let clock = Clock::from_account_info(clock_sysvar_info)?;
 
-        if vote.admin != admin_info.key.to_bytes() {
+        if vote.realm != vote_counter_info.key.to_bytes() {
+            return Err(VoteError::WrongRealm.into());
+        }
+
+        let is_over = vote.is_over(clock.unix_timestamp);
+
+        if vote.admin != admin_info.key.to_bytes() && !is_over {
             return Err(VoteError::AdminRequired.into());
         }
+
+        if vote.status != VoteStatus::Alive {
+            return Err(VoteError::DoubleClose.into());
+        }
         msg!("clock.slot: {}, vote.clock: {}", clock.slot, vote.clock);
-        if clock.slot - vote.clock >= TIME_TO_LIVE {
-            vote.status = VoteStatus::Closed;
-            vote_counter.counter -= 1;
+        if vote.is_expired(&clock, vote_counter.settings.time_to_live) {
+            Self::finalize_vote(&mut vote, &mut vote_counter, admin_info.key, &clock);
         }
 
         let _ = vote.serialize(&mut &mut vote_info.data.borrow_mut()[..]);
@@ -181,7 +739,72 @@ impl Processor {
         Ok(())
     }
 
-    fn process_create_counter(accounts: &[AccountInfo]) -> ProgramResult {
+    fn process_finalize(accounts: &[AccountInfo]) -> ProgramResult {
+        let acc_iter = &mut accounts.iter();
+        let cranker_info = next_account_info(acc_iter)?;
+        let vote_info = next_account_info(acc_iter)?;
+        let vote_counter_info = next_account_info(acc_iter)?;
+        let clock_sysvar_info = next_account_info(acc_iter)?;
+
+        if !cranker_info.is_signer {
+            return Err(VoteError::SignedRequired.into());
+        }
+
+        if vote_info.owner != &id() {
+            return Err(VoteError::WrongVoteDefine.into());
+        }
+
+        let mut vote = Vote::try_from_slice(&vote_info.data.borrow())?;
+        let mut vote_counter = Self::load_vote_counter(vote_counter_info)?;
+        let clock = Clock::from_account_info(clock_sysvar_info)?;
+
+        if vote.realm != vote_counter_info.key.to_bytes() {
+            return Err(VoteError::WrongRealm.into());
+        }
+
+        if vote.status != VoteStatus::Alive {
+            return Err(VoteError::DoubleClose.into());
+        }
+
+        if !vote.is_expired(&clock, vote_counter.settings.time_to_live) {
+            return Err(VoteError::VoteNotExpired.into());
+        }
+
+        // vote without end time expires after a few slots, so only admin may close it
+        // until realm max lifetime elapsed
+        if vote.config.end_ts.is_none()
+            && vote.admin != cranker_info.key.to_bytes()
+            && !vote
+                .is_lifetime_elapsed(clock.unix_timestamp, vote_counter.settings.max_lifetime_secs)
+        {
+            return Err(VoteError::AdminRequired.into());
+        }
+
+        Self::finalize_vote(&mut vote, &mut vote_counter, cranker_info.key, &clock);
+
+        let _ = vote.serialize(&mut &mut vote_info.data.borrow_mut()[..]);
+        let _ = vote_counter.serialize(&mut &mut vote_counter_info.data.borrow_mut()[..]);
+
+        Ok(())
+    }
+
+    fn finalize_vote(
+        vote: &mut Vote,
+        vote_counter: &mut VoteCounter,
+        closer: &Pubkey,
+        clock: &Clock,
+    ) {
+        vote.status = vote.outcome();
+        vote.closed_by = closer.to_bytes();
+        vote.closed_at = clock.unix_timestamp;
+        vote_counter.counter -= 1;
+    }
+
+    fn process_create_realm(
+        accounts: &[AccountInfo],
+        name: String,
+        settings: Settings,
+    ) -> ProgramResult {
         let acc_iter = &mut accounts.iter();
         let admin_info = next_account_info(acc_iter)?;
         let vote_counter_info = next_account_info(acc_iter)?;
@@ -192,17 +815,27 @@ impl Processor {
             return Err(VoteError::AdminRequired.into());
         }
 
-        let (vote_pubkey, bump_seed) = VoteCounter::get_vote_pubkey_with_bump();
+        if !VoteCounter::is_ok_name(&name) {
+            return Err(VoteError::WrongRealmName.into());
+        }
+
+        let (vote_pubkey, bump_seed) = VoteCounter::get_vote_pubkey_with_bump(&name);
+
+        if vote_pubkey != *vote_counter_info.key {
+            return Err(VoteError::WrongSettingsPDA.into());
+        }
 
         if !vote_counter_info.data_is_empty() {
             return Err(VoteError::DoubleCounter.into());
         }
 
-        let vote_counter = VoteCounter { counter: 0 };
+        let vote_counter =
+            VoteCounter { counter: 0, authority: admin_info.key.to_bytes(), name, settings };
         let space = vote_counter.try_to_vec()?.len();
         let rent = &Rent::from_account_info(rent_info)?;
         let lamports = rent.minimum_balance(space);
-        let signer_seeds: &[&[_]] = &[SETTINGS_SEED.as_bytes(), &[bump_seed]];
+        let signer_seeds: &[&[_]] =
+            &[SETTINGS_SEED.as_bytes(), vote_counter.name.as_bytes(), &[bump_seed]];
         invoke_signed(
             &system_instruction::create_account(
                 admin_info.key,
@@ -219,4 +852,1186 @@ impl Processor {
 
         Ok(())
     }
+
+    fn process_update_settings(accounts: &[AccountInfo], settings: Settings) -> ProgramResult {
+        let acc_iter = &mut accounts.iter();
+        let authority_info = next_account_info(acc_iter)?;
+        let vote_counter_info = next_account_info(acc_iter)?;
+
+        if !authority_info.is_signer {
+            return Err(VoteError::AuthorityRequired.into());
+        }
+
+        let mut vote_counter = Self::load_vote_counter(vote_counter_info)?;
+
+        if vote_counter.authority != authority_info.key.to_bytes() {
+            return Err(VoteError::AuthorityRequired.into());
+        }
+
+        vote_counter.settings = settings;
+
+        let _ = vote_counter.serialize(&mut &mut vote_counter_info.data.borrow_mut()[..]);
+
+        Ok(())
+    }
+
+    fn process_close_vote(accounts: &[AccountInfo]) -> ProgramResult {
+        let acc_iter = &mut accounts.iter();
+        let admin_info = next_account_info(acc_iter)?;
+        let vote_info = next_account_info(acc_iter)?;
+        let recipient_info = next_account_info(acc_iter)?;
+        let rent_info = next_account_info(acc_iter)?;
+
+        if !admin_info.is_signer {
+            return Err(VoteError::AdminRequired.into());
+        }
+
+        if vote_info.owner != &id() || vote_info.data_is_empty() {
+            return Err(VoteError::WrongVoteDefine.into());
+        }
+
+        let vote = Vote::try_from_slice(&vote_info.data.borrow())?;
+
+        if vote.admin != admin_info.key.to_bytes() {
+            return Err(VoteError::AdminRequired.into());
+        }
+
+        if !vote.status.is_final() {
+            return Err(VoteError::CloseAliveVote.into());
+        }
+
+        if vote.proposal_transactions > 0 {
+            return Err(VoteError::OpenProposalTransactions.into());
+        }
+
+        // keep empty rent-exempt tombstone, so vote can't be created again at the same PDA
+        let rent = &Rent::from_account_info(rent_info)?;
+        let lamports = vote_info.lamports() - rent.minimum_balance(0);
+        vote_info.realloc(0, false)?;
+        **vote_info.lamports.borrow_mut() -= lamports;
+        **recipient_info.lamports.borrow_mut() += lamports;
+
+        Ok(())
+    }
+
+    fn process_close_user_vote(accounts: &[AccountInfo]) -> ProgramResult {
+        let acc_iter = &mut accounts.iter();
+        let user_info = next_account_info(acc_iter)?;
+        let participate_info = next_account_info(acc_iter)?;
+        let vote_info = next_account_info(acc_iter)?;
+
+        if !user_info.is_signer {
+            return Err(VoteError::SignedRequired.into());
+        }
+
+        Self::check_ballot_key(user_info, participate_info, vote_info, acc_iter)?;
+
+        if !vote_info.data_is_empty() {
+            if vote_info.owner != &id() {
+                return Err(VoteError::WrongVoteDefine.into());
+            }
+
+            let vote = Vote::try_from_slice(&vote_info.data.borrow())?;
+            if !vote.status.is_final() {
+                return Err(VoteError::CloseAliveVote.into());
+            }
+        }
+
+        let participation = UserVotes::try_from_slice(&participate_info.data.borrow())?;
+
+        if participation.governing_mint != [0; 32] {
+            let voter_record_info = next_account_info(acc_iter)?;
+            let mint = Pubkey::new_from_array(participation.governing_mint);
+            let mut voter_record =
+                Self::load_voter_record(voter_record_info, user_info.key, &mint)?;
+            voter_record.active_votes -= 1;
+            let _ = voter_record.serialize(&mut &mut voter_record_info.data.borrow_mut()[..]);
+        }
+
+        Self::close_account(participate_info, user_info);
+
+        Ok(())
+    }
+
+    fn process_deposit(accounts: &[AccountInfo], amount: u64, lockup_secs: u64) -> ProgramResult {
+        let acc_iter = &mut accounts.iter();
+        let voter_info = next_account_info(acc_iter)?;
+        let voter_record_info = next_account_info(acc_iter)?;
+        let escrow_info = next_account_info(acc_iter)?;
+        let source_info = next_account_info(acc_iter)?;
+        let mint_info = next_account_info(acc_iter)?;
+        let rent_info = next_account_info(acc_iter)?;
+        let system_program_info = next_account_info(acc_iter)?;
+        let token_program_info = next_account_info(acc_iter)?;
+        let clock_sysvar_info = next_account_info(acc_iter)?;
+
+        if !voter_info.is_signer {
+            return Err(VoteError::SignedRequired.into());
+        }
+
+        if *token_program_info.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if lockup_secs > MAX_LOCKUP_SECS {
+            return Err(VoteError::WrongLockup.into());
+        }
+
+        let (voter_record_pubkey, voter_record_bump) =
+            VoterRecord::get_voter_record_pubkey_with_bump(voter_info.key, mint_info.key);
+
+        if voter_record_pubkey != *voter_record_info.key {
+            return Err(VoteError::WrongVoterRecordPDA.into());
+        }
+
+        let (escrow_pubkey, escrow_bump) =
+            VoterRecord::get_escrow_pubkey_with_bump(voter_info.key, mint_info.key);
+
+        if escrow_pubkey != *escrow_info.key {
+            return Err(VoteError::WrongTokenAccount.into());
+        }
+
+        if voter_record_info.data_is_empty() {
+            let voter_record = VoterRecord {
+                voter: voter_info.key.to_bytes(),
+                governing_mint: mint_info.key.to_bytes(),
+                amount: 0,
+                active_votes: 0,
+                lockup_end: 0,
+            };
+            let space = voter_record.try_to_vec()?.len();
+            let rent = &Rent::from_account_info(rent_info)?;
+            let lamports = rent.minimum_balance(space);
+            let signer_seeds: &[&[_]] = &[
+                &voter_info.key.to_bytes(),
+                &mint_info.key.to_bytes(),
+                REGISTRY_SEED.as_bytes(),
+                &[voter_record_bump],
+            ];
+            invoke_signed(
+                &system_instruction::create_account(
+                    voter_info.key,
+                    &voter_record_pubkey,
+                    lamports,
+                    space as u64,
+                    &id(),
+                ),
+                &[voter_info.clone(), voter_record_info.clone(), system_program_info.clone()],
+                &[signer_seeds],
+            )?;
+            let _ = voter_record.serialize(&mut &mut voter_record_info.data.borrow_mut()[..]);
+
+            let space = spl_token::state::Account::LEN;
+            let lamports = rent.minimum_balance(space);
+            let signer_seeds: &[&[_]] = &[
+                &voter_info.key.to_bytes(),
+                &mint_info.key.to_bytes(),
+                ESCROW_SEED.as_bytes(),
+                &[escrow_bump],
+            ];
+            invoke_signed(
+                &system_instruction::create_account(
+                    voter_info.key,
+                    &escrow_pubkey,
+                    lamports,
+                    space as u64,
+                    &spl_token::id(),
+                ),
+                &[voter_info.clone(), escrow_info.clone(), system_program_info.clone()],
+                &[signer_seeds],
+            )?;
+            invoke(
+                &spl_token::instruction::initialize_account(
+                    &spl_token::id(),
+                    &escrow_pubkey,
+                    mint_info.key,
+                    &voter_record_pubkey,
+                )?,
+                &[
+                    escrow_info.clone(),
+                    mint_info.clone(),
+                    voter_record_info.clone(),
+                    rent_info.clone(),
+                    token_program_info.clone(),
+                ],
+            )?;
+        }
+
+        let mut voter_record = VoterRecord::try_from_slice(&voter_record_info.data.borrow())?;
+
+        invoke(
+            &spl_token::instruction::transfer(
+                &spl_token::id(),
+                source_info.key,
+                &escrow_pubkey,
+                voter_info.key,
+                &[],
+                amount,
+            )?,
+            &[
+                source_info.clone(),
+                escrow_info.clone(),
+                voter_info.clone(),
+                token_program_info.clone(),
+            ],
+        )?;
+
+        voter_record.amount += amount;
+
+        if lockup_secs > 0 {
+            let unix_timestamp = Clock::from_account_info(clock_sysvar_info)?.unix_timestamp;
+            let lockup_end = unix_timestamp + lockup_secs as i64;
+            voter_record.lockup_end = voter_record.lockup_end.max(lockup_end);
+        }
+
+        let _ = voter_record.serialize(&mut &mut voter_record_info.data.borrow_mut()[..]);
+
+        Ok(())
+    }
+
+    fn process_withdraw(accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+        let acc_iter = &mut accounts.iter();
+        let voter_info = next_account_info(acc_iter)?;
+        let voter_record_info = next_account_info(acc_iter)?;
+        let escrow_info = next_account_info(acc_iter)?;
+        let destination_info = next_account_info(acc_iter)?;
+        let token_program_info = next_account_info(acc_iter)?;
+        let clock_sysvar_info = next_account_info(acc_iter)?;
+
+        if !voter_info.is_signer {
+            return Err(VoteError::SignedRequired.into());
+        }
+
+        if *token_program_info.key != spl_token::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let mut voter_record = VoterRecord::try_from_slice(&voter_record_info.data.borrow())?;
+        let mint = Pubkey::new_from_array(voter_record.governing_mint);
+        let (voter_record_pubkey, voter_record_bump) =
+            VoterRecord::get_voter_record_pubkey_with_bump(voter_info.key, &mint);
+
+        if voter_record_pubkey != *voter_record_info.key {
+            return Err(VoteError::WrongVoterRecordPDA.into());
+        }
+
+        if VoterRecord::get_escrow_pubkey(voter_info.key, &mint) != *escrow_info.key {
+            return Err(VoteError::WrongTokenAccount.into());
+        }
+
+        if voter_record.active_votes > 0 {
+            return Err(VoteError::WithdrawWithActiveVotes.into());
+        }
+
+        if Clock::from_account_info(clock_sysvar_info)?.unix_timestamp < voter_record.lockup_end {
+            return Err(VoteError::DepositLocked.into());
+        }
+
+        if amount > voter_record.amount {
+            return Err(VoteError::InsufficientDeposit.into());
+        }
+
+        let signer_seeds: &[&[_]] = &[
+            &voter_info.key.to_bytes(),
+            &mint.to_bytes(),
+            REGISTRY_SEED.as_bytes(),
+            &[voter_record_bump],
+        ];
+        invoke_signed(
+            &spl_token::instruction::transfer(
+                &spl_token::id(),
+                escrow_info.key,
+                destination_info.key,
+                &voter_record_pubkey,
+                &[],
+                amount,
+            )?,
+            &[
+                escrow_info.clone(),
+                destination_info.clone(),
+                voter_record_info.clone(),
+                token_program_info.clone(),
+            ],
+            &[signer_seeds],
+        )?;
+
+        voter_record.amount -= amount;
+
+        let _ = voter_record.serialize(&mut &mut voter_record_info.data.borrow_mut()[..]);
+
+        Ok(())
+    }
+
+    fn process_tally(accounts: &[AccountInfo]) -> ProgramResult {
+        let acc_iter = &mut accounts.iter();
+        let vote_info = next_account_info(acc_iter)?;
+
+        if vote_info.owner != &id() {
+            return Err(VoteError::WrongVoteDefine.into());
+        }
+
+        let mut vote = Vote::try_from_slice(&vote_info.data.borrow())?;
+
+        if vote.status != VoteStatus::Tallying {
+            return Err(VoteError::NotTallying.into());
+        }
+
+        let tally = vote.ranked_tally.as_mut().ok_or(VoteError::NotTallying)?;
+
+        while let Some(user_info) = acc_iter.next() {
+            let participate_info = next_account_info(acc_iter)?;
+
+            // ballot of anonymous vote is keyed by nullifier
+            let participate_pubkey = match vote.config.anonymous_root {
+                Some(_) => {
+                    UserVotes::get_nullifier_pubkey(&user_info.key.to_bytes(), vote_info.key)
+                }
+                None => UserVotes::get_uservote_pubkey(user_info.key, vote_info.key),
+            };
+
+            if participate_pubkey != *participate_info.key {
+                return Err(VoteError::WrongUserVotePDA.into());
+            }
+
+            let mut participation = UserVotes::try_from_slice(&participate_info.data.borrow())?;
+
+            if !participation.is_ranked() || participation.tallied_rounds != tally.round {
+                continue;
+            }
+
+            if let Some(option) = tally.preference(&participation.ranking) {
+                let count = &mut tally.rounds[tally.round as usize][option as usize];
+                *count = count.checked_add(participation.weight).ok_or(VoteError::TallyOverflow)?;
+            }
+
+            tally.counted += 1;
+            participation.tallied_rounds += 1;
+            let _ = participation.serialize(&mut &mut participate_info.data.borrow_mut()[..]);
+        }
+
+        if tally.counted == tally.ballots && tally.close_round() {
+            vote.status = match tally.winner {
+                NO_WINNER => VoteStatus::Rejected,
+                _ => VoteStatus::Passed,
+            };
+        }
+
+        let _ = vote.serialize(&mut &mut vote_info.data.borrow_mut()[..]);
+
+        Ok(())
+    }
+
+    fn process_retract_vote(accounts: &[AccountInfo]) -> ProgramResult {
+        let acc_iter = &mut accounts.iter();
+        let user_info = next_account_info(acc_iter)?;
+        let participate_info = next_account_info(acc_iter)?;
+        let vote_info = next_account_info(acc_iter)?;
+        let clock_sysvar_info = next_account_info(acc_iter)?;
+
+        if !user_info.is_signer {
+            return Err(VoteError::SignedRequired.into());
+        }
+
+        Self::check_ballot_key(user_info, participate_info, vote_info, acc_iter)?;
+
+        if vote_info.owner != &id() {
+            return Err(VoteError::WrongVoteDefine.into());
+        }
+
+        let mut participation = UserVotes::try_from_slice(&participate_info.data.borrow())?;
+        let mut vote = Vote::try_from_slice(&vote_info.data.borrow())?;
+        let clock = Clock::from_account_info(clock_sysvar_info)?;
+
+        if !participation.is_voted() {
+            return Err(VoteError::NotVoted.into());
+        }
+
+        if vote.status != VoteStatus::Alive {
+            return Err(VoteError::CloseVoteParticipate.into());
+        }
+
+        if vote.is_ended(clock.unix_timestamp) {
+            return Err(VoteError::VoteEnded.into());
+        }
+
+        Self::remove_ballot(&mut vote, &mut participation)?;
+
+        if participation.governing_mint != [0; 32] {
+            let voter_record_info = next_account_info(acc_iter)?;
+            let mint = Pubkey::new_from_array(participation.governing_mint);
+            let mut voter_record =
+                Self::load_voter_record(voter_record_info, user_info.key, &mint)?;
+
+            voter_record.active_votes -= 1;
+            participation.governing_mint = [0; 32];
+            let _ = voter_record.serialize(&mut &mut voter_record_info.data.borrow_mut()[..]);
+        }
+
+        let _ = participation.serialize(&mut &mut participate_info.data.borrow_mut()[..]);
+        let _ = vote.serialize(&mut &mut vote_info.data.borrow_mut()[..]);
+
+        Ok(())
+    }
+
+    fn process_vote_anonymous(
+        accounts: &[AccountInfo],
+        direction: Direction,
+        nullifier: [u8; 32],
+        proof: AnonymousProof,
+    ) -> ProgramResult {
+        let acc_iter = &mut accounts.iter();
+        let payer_info = next_account_info(acc_iter)?;
+        let participate_info = next_account_info(acc_iter)?;
+        let vote_info = next_account_info(acc_iter)?;
+        let rent_info = next_account_info(acc_iter)?;
+        let system_program_info = next_account_info(acc_iter)?;
+        let clock_sysvar_info = next_account_info(acc_iter)?;
+
+        if !payer_info.is_signer {
+            return Err(VoteError::SignedRequired.into());
+        }
+
+        if vote_info.owner != &id() {
+            return Err(VoteError::WrongVoteDefine.into());
+        }
+
+        let mut vote = Vote::try_from_slice(&vote_info.data.borrow())?;
+        let root = vote.config.anonymous_root.ok_or(VoteError::WrongVotingMode)?;
+
+        match proof {
+            AnonymousProof::Signer { path } => {
+                let voter_key_info = next_account_info(acc_iter)?;
+
+                if !voter_key_info.is_signer {
+                    return Err(VoteError::SignedRequired.into());
+                }
+
+                let leaf = anonymous_leaf(voter_key_info.key);
+                let node = path.iter().fold(leaf, |node, sibling| allowlist_node(&node, sibling));
+
+                if node != root {
+                    return Err(VoteError::NotAllowlisted.into());
+                }
+
+                if anonymous_nullifier(voter_key_info.key, vote_info.key) != nullifier {
+                    return Err(VoteError::WrongNullifier.into());
+                }
+            }
+            AnonymousProof::Verifier { proof } => {
+                let verifier_info = next_account_info(acc_iter)?;
+
+                if vote.config.anonymous_verifier != Some(*verifier_info.key) {
+                    return Err(VoteError::WrongVerifier.into());
+                }
+
+                let statement = AnonymousStatement {
+                    root,
+                    nullifier,
+                    vote: *vote_info.key,
+                    ballot: hashv(&[&direction.try_to_vec()?]).to_bytes(),
+                    proof,
+                };
+                invoke(
+                    &Instruction::new_with_borsh(*verifier_info.key, &statement, vec![]),
+                    std::slice::from_ref(verifier_info),
+                )?;
+            }
+        }
+
+        let (participate_pubkey, bump_seed) =
+            UserVotes::get_nullifier_pubkey_with_bump(&nullifier, vote_info.key);
+
+        if participate_pubkey != *participate_info.key {
+            return Err(VoteError::WrongUserVotePDA.into());
+        }
+
+        if !participate_info.data_is_empty() {
+            return Err(VoteError::DoubleParticipate.into());
+        }
+
+        let mut participation = UserVotes::default();
+        let space = participation.try_to_vec()?.len();
+        let rent = &Rent::from_account_info(rent_info)?;
+        let lamports = rent.minimum_balance(space);
+        let signer_seeds: &[&[_]] =
+            &[&nullifier, &vote_info.key.to_bytes(), NULLIFIER_SEED.as_bytes(), &[bump_seed]];
+        invoke_signed(
+            &system_instruction::create_account(
+                payer_info.key,
+                &participate_pubkey,
+                lamports,
+                space as u64,
+                &id(),
+            ),
+            &[payer_info.clone(), participate_info.clone(), system_program_info.clone()],
+            &[signer_seeds],
+        )?;
+
+        let clock = Clock::from_account_info(clock_sysvar_info)?;
+
+        if vote.status != VoteStatus::Alive {
+            return Err(VoteError::CloseVoteParticipate.into());
+        }
+
+        if !vote.is_started(clock.unix_timestamp) {
+            return Err(VoteError::VoteNotStarted.into());
+        }
+
+        if vote.is_ended(clock.unix_timestamp) {
+            return Err(VoteError::VoteEnded.into());
+        }
+
+        Self::cast_ballot(&mut vote, &mut participation, &direction, 1, None)?;
+        participation.cast_by = payer_info.key.to_bytes();
+
+        let _ = participation.serialize(&mut &mut participate_info.data.borrow_mut()[..]);
+        let _ = vote.serialize(&mut &mut vote_info.data.borrow_mut()[..]);
+
+        Ok(())
+    }
+
+    fn process_close_nullifier(accounts: &[AccountInfo], nullifier: [u8; 32]) -> ProgramResult {
+        let acc_iter = &mut accounts.iter();
+        let payer_info = next_account_info(acc_iter)?;
+        let participate_info = next_account_info(acc_iter)?;
+        let vote_info = next_account_info(acc_iter)?;
+
+        if !payer_info.is_signer {
+            return Err(VoteError::SignedRequired.into());
+        }
+
+        let participate_pubkey = UserVotes::get_nullifier_pubkey(&nullifier, vote_info.key);
+        if participate_pubkey != *participate_info.key
+            || participate_info.owner != &id()
+            || participate_info.data_is_empty()
+        {
+            return Err(VoteError::WrongUserVotePDA.into());
+        }
+
+        if !vote_info.data_is_empty() {
+            if vote_info.owner != &id() {
+                return Err(VoteError::WrongVoteDefine.into());
+            }
+
+            let vote = Vote::try_from_slice(&vote_info.data.borrow())?;
+            if !vote.status.is_final() {
+                return Err(VoteError::CloseAliveVote.into());
+            }
+        }
+
+        let participation = UserVotes::try_from_slice(&participate_info.data.borrow())?;
+
+        if participation.cast_by != payer_info.key.to_bytes() {
+            return Err(VoteError::SignedRequired.into());
+        }
+
+        Self::close_account(participate_info, payer_info);
+
+        Ok(())
+    }
+
+    fn process_reveal(
+        accounts: &[AccountInfo],
+        direction: Direction,
+        salt: [u8; 32],
+    ) -> ProgramResult {
+        let acc_iter = &mut accounts.iter();
+        let user_info = next_account_info(acc_iter)?;
+        let participate_info = next_account_info(acc_iter)?;
+        let vote_info = next_account_info(acc_iter)?;
+        let clock_sysvar_info = next_account_info(acc_iter)?;
+
+        if !user_info.is_signer {
+            return Err(VoteError::SignedRequired.into());
+        }
+
+        let ballot_key = Self::check_ballot_key(user_info, participate_info, vote_info, acc_iter)?;
+
+        if vote_info.owner != &id() {
+            return Err(VoteError::WrongVoteDefine.into());
+        }
+
+        let mut participation = UserVotes::try_from_slice(&participate_info.data.borrow())?;
+        let mut vote = Vote::try_from_slice(&vote_info.data.borrow())?;
+        let clock = Clock::from_account_info(clock_sysvar_info)?;
+
+        if vote.status != VoteStatus::Alive || !vote.is_revealing(clock.unix_timestamp) {
+            return Err(VoteError::RevealNotOpen.into());
+        }
+
+        if participation.ballot != BallotKind::Commit
+            || matches!(direction, Direction::Commit(_))
+            || direction.commitment(vote_info.key, &ballot_key, &salt) != participation.commitment
+        {
+            return Err(VoteError::WrongReveal.into());
+        }
+
+        let votes = participation.weight;
+        let credits_spent = participation.credits_spent;
+        Self::remove_ballot(&mut vote, &mut participation)?;
+        participation.credits_spent = credits_spent;
+        Self::add_ballot(&mut vote, &mut participation, &direction, votes)?;
+
+        let _ = participation.serialize(&mut &mut participate_info.data.borrow_mut()[..]);
+        let _ = vote.serialize(&mut &mut vote_info.data.borrow_mut()[..]);
+
+        Ok(())
+    }
+
+    fn process_add_voter(accounts: &[AccountInfo], voter: Pubkey) -> ProgramResult {
+        let acc_iter = &mut accounts.iter();
+        let authority_info = next_account_info(acc_iter)?;
+        let vote_info = next_account_info(acc_iter)?;
+        let vote_counter_info = next_account_info(acc_iter)?;
+        let membership_info = next_account_info(acc_iter)?;
+        let rent_info = next_account_info(acc_iter)?;
+        let system_program_info = next_account_info(acc_iter)?;
+
+        Self::check_vote_authority(authority_info, vote_info, vote_counter_info)?;
+
+        let (membership_pubkey, bump_seed) =
+            Membership::get_membership_pubkey_with_bump(vote_info.key, &voter);
+
+        if membership_pubkey != *membership_info.key {
+            return Err(VoteError::WrongMembershipPDA.into());
+        }
+
+        if !membership_info.data_is_empty() {
+            return Err(VoteError::DoubleMember.into());
+        }
+
+        let membership = Membership { vote: vote_info.key.to_bytes(), voter: voter.to_bytes() };
+        let space = membership.try_to_vec()?.len();
+        let rent = &Rent::from_account_info(rent_info)?;
+        let lamports = rent.minimum_balance(space);
+        let signer_seeds: &[&[_]] =
+            &[&vote_info.key.to_bytes(), &voter.to_bytes(), MEMBER_SEED.as_bytes(), &[bump_seed]];
+        invoke_signed(
+            &system_instruction::create_account(
+                authority_info.key,
+                &membership_pubkey,
+                lamports,
+                space as u64,
+                &id(),
+            ),
+            &[authority_info.clone(), membership_info.clone(), system_program_info.clone()],
+            &[signer_seeds],
+        )?;
+
+        let _ = membership.serialize(&mut &mut membership_info.data.borrow_mut()[..]);
+
+        Ok(())
+    }
+
+    fn process_remove_voter(accounts: &[AccountInfo], voter: Pubkey) -> ProgramResult {
+        let acc_iter = &mut accounts.iter();
+        let authority_info = next_account_info(acc_iter)?;
+        let vote_info = next_account_info(acc_iter)?;
+        let vote_counter_info = next_account_info(acc_iter)?;
+        let membership_info = next_account_info(acc_iter)?;
+
+        Self::check_vote_authority(authority_info, vote_info, vote_counter_info)?;
+        Self::load_membership(membership_info, vote_info.key, &voter)?;
+        Self::close_account(membership_info, authority_info);
+
+        Ok(())
+    }
+
+    fn process_add_proposal_transaction(
+        accounts: &[AccountInfo],
+        index: u16,
+        instruction: ProposalInstruction,
+    ) -> ProgramResult {
+        let acc_iter = &mut accounts.iter();
+        let admin_info = next_account_info(acc_iter)?;
+        let authority_info = next_account_info(acc_iter)?;
+        let vote_counter_info = next_account_info(acc_iter)?;
+        let vote_info = next_account_info(acc_iter)?;
+        let proposal_info = next_account_info(acc_iter)?;
+        let rent_info = next_account_info(acc_iter)?;
+        let system_program_info = next_account_info(acc_iter)?;
+
+        if !admin_info.is_signer {
+            return Err(VoteError::AdminRequired.into());
+        }
+
+        if vote_info.owner != &id() {
+            return Err(VoteError::WrongVoteDefine.into());
+        }
+
+        let mut vote = Vote::try_from_slice(&vote_info.data.borrow())?;
+        let vote_counter = Self::load_vote_counter(vote_counter_info)?;
+
+        if vote.admin != admin_info.key.to_bytes() {
+            return Err(VoteError::AdminRequired.into());
+        }
+
+        if vote.realm != vote_counter_info.key.to_bytes() {
+            return Err(VoteError::WrongRealm.into());
+        }
+
+        // anyone can create vote in realm, but only realm authority spends its governance PDA
+        if !authority_info.is_signer || vote_counter.authority != authority_info.key.to_bytes() {
+            return Err(VoteError::AuthorityRequired.into());
+        }
+
+        // passed poll only tells which option won, not that instructions were approved
+        if vote.is_poll() || vote.config.mode != VotingMode::Simple {
+            return Err(VoteError::WrongVotingMode.into());
+        }
+
+        // voters must see every instruction they vote on
+        if vote.status != VoteStatus::Alive || vote.has_ballots() {
+            return Err(VoteError::ProposalHasBallots.into());
+        }
+
+        let (proposal_pubkey, bump_seed) =
+            ProposalTransaction::get_proposal_transaction_pubkey_with_bump(vote_info.key, index);
+
+        if proposal_pubkey != *proposal_info.key {
+            return Err(VoteError::WrongProposalTransactionPDA.into());
+        }
+
+        let proposal = ProposalTransaction {
+            vote: vote_info.key.to_bytes(),
+            index,
+            instruction,
+            executed_at: 0,
+        };
+        let space = proposal.try_to_vec()?.len();
+        let rent = &Rent::from_account_info(rent_info)?;
+        let lamports = rent.minimum_balance(space);
+        let signer_seeds: &[&[_]] = &[
+            &vote_info.key.to_bytes(),
+            &index.to_le_bytes(),
+            PROPOSAL_SEED.as_bytes(),
+            &[bump_seed],
+        ];
+        invoke_signed(
+            &system_instruction::create_account(
+                admin_info.key,
+                &proposal_pubkey,
+                lamports,
+                space as u64,
+                &id(),
+            ),
+            &[admin_info.clone(), proposal_info.clone(), system_program_info.clone()],
+            &[signer_seeds],
+        )?;
+
+        let _ = proposal.serialize(&mut &mut proposal_info.data.borrow_mut()[..]);
+
+        vote.proposal_transactions =
+            vote.proposal_transactions.checked_add(1).ok_or(VoteError::TallyOverflow)?;
+        let _ = vote.serialize(&mut &mut vote_info.data.borrow_mut()[..]);
+
+        Ok(())
+    }
+
+    fn process_execute(accounts: &[AccountInfo]) -> ProgramResult {
+        let acc_iter = &mut accounts.iter();
+        let vote_info = next_account_info(acc_iter)?;
+        let governance_info = next_account_info(acc_iter)?;
+        let proposal_info = next_account_info(acc_iter)?;
+        let clock_sysvar_info = next_account_info(acc_iter)?;
+
+        if vote_info.owner != &id() {
+            return Err(VoteError::WrongVoteDefine.into());
+        }
+
+        let vote = Vote::try_from_slice(&vote_info.data.borrow())?;
+
+        if proposal_info.owner != &id() || proposal_info.data_is_empty() {
+            return Err(VoteError::WrongProposalTransactionPDA.into());
+        }
+
+        let mut proposal = ProposalTransaction::try_from_slice(&proposal_info.data.borrow())?;
+
+        if proposal.vote != vote_info.key.to_bytes()
+            || ProposalTransaction::get_proposal_transaction_pubkey(vote_info.key, proposal.index)
+                != *proposal_info.key
+        {
+            return Err(VoteError::WrongProposalTransactionPDA.into());
+        }
+
+        let realm = Pubkey::new_from_array(vote.realm);
+        let (governance_pubkey, bump_seed) = VoteCounter::get_governance_pubkey_with_bump(&realm);
+
+        if governance_pubkey != *governance_info.key {
+            return Err(VoteError::WrongGovernancePDA.into());
+        }
+
+        if proposal.is_executed() {
+            return Err(VoteError::DoubleExecute.into());
+        }
+
+        let clock = Clock::from_account_info(clock_sysvar_info)?;
+
+        if !vote.is_executable(clock.unix_timestamp) {
+            return Err(VoteError::NotExecutable.into());
+        }
+
+        // remaining accounts are accounts of the instruction and its program
+        let mut account_infos = vec![governance_info.clone()];
+        account_infos.extend(acc_iter.cloned());
+        let signer_seeds: &[&[_]] = &[&vote.realm, GOVERNANCE_SEED.as_bytes(), &[bump_seed]];
+        invoke_signed(&Instruction::from(&proposal.instruction), &account_infos, &[signer_seeds])?;
+
+        proposal.executed_at = clock.unix_timestamp;
+        let _ = proposal.serialize(&mut &mut proposal_info.data.borrow_mut()[..]);
+
+        Ok(())
+    }
+
+    fn process_close_proposal_transaction(accounts: &[AccountInfo]) -> ProgramResult {
+        let acc_iter = &mut accounts.iter();
+        let authority_info = next_account_info(acc_iter)?;
+        let vote_counter_info = next_account_info(acc_iter)?;
+        let vote_info = next_account_info(acc_iter)?;
+        let proposal_info = next_account_info(acc_iter)?;
+        let clock_sysvar_info = next_account_info(acc_iter)?;
+
+        Self::check_vote_authority(authority_info, vote_info, vote_counter_info)?;
+
+        let mut vote = Vote::try_from_slice(&vote_info.data.borrow())?;
+        let clock = Clock::from_account_info(clock_sysvar_info)?;
+
+        if proposal_info.owner != &id() || proposal_info.data_is_empty() {
+            return Err(VoteError::WrongProposalTransactionPDA.into());
+        }
+
+        let proposal = ProposalTransaction::try_from_slice(&proposal_info.data.borrow())?;
+
+        if proposal.vote != vote_info.key.to_bytes()
+            || ProposalTransaction::get_proposal_transaction_pubkey(vote_info.key, proposal.index)
+                != *proposal_info.key
+        {
+            return Err(VoteError::WrongProposalTransactionPDA.into());
+        }
+
+        // transaction of passed vote is closed only after it ran, or is cancelled
+        // if it could not run during grace period, so the vote can still be closed
+        let is_dropped = vote.status.is_final() && vote.status != VoteStatus::Passed;
+        let is_cancelled = vote.is_execution_expired(clock.unix_timestamp);
+        if !proposal.is_executed() && !is_dropped && !is_cancelled {
+            return Err(VoteError::ProposalTransactionPending.into());
+        }
+
+        Self::close_account(proposal_info, authority_info);
+        vote.proposal_transactions -= 1;
+
+        let _ = vote.serialize(&mut &mut vote_info.data.borrow_mut()[..]);
+
+        Ok(())
+    }
+
+    /// Signer must be admin of vote or authority of its realm
+    fn check_vote_authority(
+        authority_info: &AccountInfo,
+        vote_info: &AccountInfo,
+        vote_counter_info: &AccountInfo,
+    ) -> ProgramResult {
+        if !authority_info.is_signer {
+            return Err(VoteError::AdminRequired.into());
+        }
+
+        if vote_info.owner != &id() {
+            return Err(VoteError::WrongVoteDefine.into());
+        }
+
+        let vote = Vote::try_from_slice(&vote_info.data.borrow())?;
+        let vote_counter = Self::load_vote_counter(vote_counter_info)?;
+
+        if vote.realm != vote_counter_info.key.to_bytes() {
+            return Err(VoteError::WrongRealm.into());
+        }
+
+        let authority = authority_info.key.to_bytes();
+        if vote.admin != authority && vote_counter.authority != authority {
+            return Err(VoteError::AdminRequired.into());
+        }
+
+        Ok(())
+    }
+
+    fn load_membership(
+        membership_info: &AccountInfo,
+        vote: &Pubkey,
+        voter: &Pubkey,
+    ) -> Result<Membership, ProgramError> {
+        if Membership::get_membership_pubkey(vote, voter) != *membership_info.key {
+            return Err(VoteError::WrongMembershipPDA.into());
+        }
+
+        if membership_info.owner != &id() || membership_info.data_is_empty() {
+            return Err(VoteError::NotMember.into());
+        }
+
+        let membership = Membership::try_from_slice(&membership_info.data.borrow())?;
+
+        if membership.voter != voter.to_bytes() {
+            return Err(VoteError::NotMember.into());
+        }
+
+        Ok(membership)
+    }
+
+    /// `UserVotes` is derived from user, or from mint of NFT held by user in collection-gated
+    /// vote, then the token account of NFT follows fixed accounts. Returns the key it is
+    /// derived from
+    fn check_ballot_key<'a>(
+        user_info: &AccountInfo<'a>,
+        participate_info: &AccountInfo<'a>,
+        vote_info: &AccountInfo<'a>,
+        acc_iter: &mut std::slice::Iter<AccountInfo<'a>>,
+    ) -> Result<Pubkey, ProgramError> {
+        if UserVotes::get_uservote_pubkey(user_info.key, vote_info.key) == *participate_info.key {
+            return Ok(*user_info.key);
+        }
+
+        let token_info = next_account_info(acc_iter)?;
+        let mint = Self::nft_mint(user_info.key, token_info)?;
+
+        if UserVotes::get_uservote_pubkey(&mint, vote_info.key) != *participate_info.key {
+            return Err(VoteError::WrongUserVotePDA.into());
+        }
+
+        Ok(mint)
+    }
+
+    /// Mint of NFT in token account owned by user
+    fn nft_mint(user: &Pubkey, token_info: &AccountInfo) -> Result<Pubkey, ProgramError> {
+        if token_info.owner != &spl_token::id() {
+            return Err(VoteError::WrongTokenAccount.into());
+        }
+
+        let token_account = spl_token::state::Account::unpack(&token_info.data.borrow())?;
+
+        if token_account.owner != *user || token_account.amount != 1 {
+            return Err(VoteError::NotNftHolder.into());
+        }
+
+        Ok(token_account.mint)
+    }
+
+    fn check_collection(
+        metadata_info: &AccountInfo,
+        mint: &Pubkey,
+        collection: &Pubkey,
+    ) -> ProgramResult {
+        if Metadata::get_metadata_pubkey(mint) != *metadata_info.key
+            || metadata_info.owner != &metadata_program::id()
+        {
+            return Err(VoteError::WrongMetadata.into());
+        }
+
+        let metadata = Metadata::from_account_data(&metadata_info.data.borrow())
+            .ok_or(VoteError::WrongMetadata)?;
+
+        if metadata.mint != *mint || !metadata.is_verified_in(collection) {
+            return Err(VoteError::WrongCollection.into());
+        }
+
+        Ok(())
+    }
+
+    fn process_delegate(accounts: &[AccountInfo], delegate: Pubkey) -> ProgramResult {
+        let acc_iter = &mut accounts.iter();
+        let delegator_info = next_account_info(acc_iter)?;
+        let vote_counter_info = next_account_info(acc_iter)?;
+        let delegation_info = next_account_info(acc_iter)?;
+        let rent_info = next_account_info(acc_iter)?;
+        let system_program_info = next_account_info(acc_iter)?;
+
+        if !delegator_info.is_signer {
+            return Err(VoteError::SignedRequired.into());
+        }
+
+        if delegate == *delegator_info.key {
+            return Err(VoteError::SelfDelegation.into());
+        }
+
+        Self::load_vote_counter(vote_counter_info)?;
+
+        let (delegation_pubkey, bump_seed) =
+            Delegation::get_delegation_pubkey_with_bump(vote_counter_info.key, delegator_info.key);
+
+        if delegation_pubkey != *delegation_info.key {
+            return Err(VoteError::WrongDelegationPDA.into());
+        }
+
+        let delegation = Delegation {
+            realm: vote_counter_info.key.to_bytes(),
+            delegator: delegator_info.key.to_bytes(),
+            delegate: delegate.to_bytes(),
+        };
+
+        if delegation_info.data_is_empty() {
+            let space = delegation.try_to_vec()?.len();
+            let rent = &Rent::from_account_info(rent_info)?;
+            let lamports = rent.minimum_balance(space);
+            let signer_seeds: &[&[_]] = &[
+                &vote_counter_info.key.to_bytes(),
+                &delegator_info.key.to_bytes(),
+                DELEGATION_SEED.as_bytes(),
+                &[bump_seed],
+            ];
+            invoke_signed(
+                &system_instruction::create_account(
+                    delegator_info.key,
+                    &delegation_pubkey,
+                    lamports,
+                    space as u64,
+                    &id(),
+                ),
+                &[delegator_info.clone(), delegation_info.clone(), system_program_info.clone()],
+                &[signer_seeds],
+            )?;
+        }
+
+        let _ = delegation.serialize(&mut &mut delegation_info.data.borrow_mut()[..]);
+
+        Ok(())
+    }
+
+    fn process_revoke_delegation(accounts: &[AccountInfo]) -> ProgramResult {
+        let acc_iter = &mut accounts.iter();
+        let delegator_info = next_account_info(acc_iter)?;
+        let vote_counter_info = next_account_info(acc_iter)?;
+        let delegation_info = next_account_info(acc_iter)?;
+
+        if !delegator_info.is_signer {
+            return Err(VoteError::SignedRequired.into());
+        }
+
+        if Delegation::get_delegation_pubkey(vote_counter_info.key, delegator_info.key)
+            != *delegation_info.key
+        {
+            return Err(VoteError::WrongDelegationPDA.into());
+        }
+
+        if delegation_info.owner != &id() || delegation_info.data_is_empty() {
+            return Err(VoteError::NotDelegate.into());
+        }
+
+        Self::close_account(delegation_info, delegator_info);
+
+        Ok(())
+    }
+
+    fn load_delegation(
+        delegation_info: &AccountInfo,
+        realm: &Pubkey,
+        delegator: &Pubkey,
+        delegate: &Pubkey,
+    ) -> ProgramResult {
+        if Delegation::get_delegation_pubkey(realm, delegator) != *delegation_info.key {
+            return Err(VoteError::WrongDelegationPDA.into());
+        }
+
+        if delegation_info.owner != &id() || delegation_info.data_is_empty() {
+            return Err(VoteError::NotDelegate.into());
+        }
+
+        let delegation = Delegation::try_from_slice(&delegation_info.data.borrow())?;
+
+        if delegation.delegate != delegate.to_bytes() {
+            return Err(VoteError::NotDelegate.into());
+        }
+
+        Ok(())
+    }
+
+    /// Check that instruction preceding current one is Ed25519 program verifying signature
+    /// of `voter` over `message`, the program fails whole transaction on bad signature
+    fn check_relay_signature(
+        instructions_info: &AccountInfo,
+        voter: &Pubkey,
+        message: &[u8],
+    ) -> ProgramResult {
+        if *instructions_info.key != instructions::id() {
+            return Err(VoteError::WrongRelaySignature.into());
+        }
+
+        let current = instructions::load_current_index_checked(instructions_info)? as usize;
+        let verify = current
+            .checked_sub(1)
+            .map(|index| instructions::load_instruction_at_checked(index, instructions_info))
+            .transpose()?
+            .filter(|verify| verify.program_id == ed25519_program::id())
+            .ok_or(VoteError::WrongRelaySignature)?;
+
+        // single signature offsets follow count and padding bytes, all data must be in
+        // the Ed25519 instruction itself
+        let data = &verify.data;
+        let offset = |at: usize| {
+            data.get(at..at + 2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+        };
+        let field =
+            |at: usize, len: usize| offset(at).and_then(|start| data.get(start..start + len));
+
+        let signed_in_place = [4, 8, 14].iter().all(|&at| offset(at) == Some(u16::MAX as usize));
+        let public_key = field(6, 32);
+        let signed_message = offset(12).and_then(|len| field(10, len));
+
+        if data.first() != Some(&1)
+            || !signed_in_place
+            || public_key != Some(voter.as_ref())
+            || signed_message != Some(message)
+        {
+            return Err(VoteError::WrongRelaySignature.into());
+        }
+
+        Ok(())
+    }
+
+    fn load_voter_record(
+        voter_record_info: &AccountInfo,
+        voter: &Pubkey,
+        mint: &Pubkey,
+    ) -> Result<VoterRecord, ProgramError> {
+        if VoterRecord::get_voter_record_pubkey(voter, mint) != *voter_record_info.key {
+            return Err(VoteError::WrongVoterRecordPDA.into());
+        }
+
+        Ok(VoterRecord::try_from_slice(&voter_record_info.data.borrow())?)
+    }
+
+    fn load_vote_counter(vote_counter_info: &AccountInfo) -> Result<VoteCounter, ProgramError> {
+        let vote_counter = VoteCounter::try_from_slice(&vote_counter_info.data.borrow())?;
+
+        if !VoteCounter::is_ok_vote_pubkey(&vote_counter.name, vote_counter_info.key) {
+            return Err(VoteError::WrongSettingsPDA.into());
+        }
+
+        Ok(vote_counter)
+    }
+
+    fn close_account(account_info: &AccountInfo, recipient_info: &AccountInfo) {
+        let lamports = account_info.lamports();
+        **account_info.lamports.borrow_mut() = 0;
+        **recipient_info.lamports.borrow_mut() += lamports;
+        account_info.data.borrow_mut().fill(0);
+    }
+}
+
+/// Integer square root, rounded down
+fn isqrt(value: u64) -> u64 {
+    if value < 2 {
+        return value;
+    }
+
+    let mut root = value;
+    let mut next = value / 2 + value % 2;
+    while next < root {
+        root = next;
+        next = (root + value / root) / 2;
+    }
+
+    root
+}
+
+fn take_votes(tally: &mut u64, votes: u64) -> ProgramResult {
+    *tally = tally.checked_sub(votes).ok_or(VoteError::TallyOverflow)?;
+    Ok(())
 }
@@ -1,14 +1,109 @@
 use borsh::{BorshDeserialize, BorshSerialize};
-use solana_program::pubkey::Pubkey;
+use solana_program::{
+    clock::Clock,
+    hash::hashv,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
 
-use crate::{id, SETTINGS_SEED, VOTE_SEED};
+use crate::{
+    id, DELEGATION_SEED, ESCROW_SEED, GOVERNANCE_SEED, MEMBER_SEED, NULLIFIER_SEED, PROPOSAL_SEED,
+    REGISTRY_SEED, SETTINGS_SEED, VOTE_SEED,
+};
+
+/// Kind of ballot cast by user, its content is kept in matching `UserVotes` fields
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq)]
+pub enum BallotKind {
+    None,
+    For,
+    Against,
+    Abstain,
+    Choice,
+    Ranking,
+    Approve,
+    Scores,
+    Commit,
+}
 
 #[derive(BorshSerialize, BorshDeserialize, Debug)]
 pub struct UserVotes {
-    pub is_voted: bool,
+    pub ballot: BallotKind,
+
+    /// Index of chosen poll option if ballot is `Choice`
+    pub choice: u8,
+
+    /// Voting power counted for the ballot
+    pub weight: u64,
+
+    /// Mint of voter record the weight was taken from, zeroes if vote is not token-weighted
+    pub governing_mint: [u8; 32],
+
+    /// Voice credits spent in quadratic vote
+    pub credits_spent: u64,
+
+    /// Option indices of ranked ballot in order of preference, padded with `UNRANKED`
+    pub ranking: [u8; MAX_OPTIONS],
+
+    /// Count of tally rounds the ranked ballot was counted in
+    pub tallied_rounds: u8,
+
+    /// Bitmask of poll options approved in approval vote
+    pub approvals: u16,
+
+    /// Score of every poll option in score vote
+    pub scores: [u8; MAX_OPTIONS],
+
+    /// Delegate who cast the ballot, zeroes if cast by user, or payer of anonymous ballot
+    pub cast_by: [u8; 32],
+
+    /// Hash of ballot and salt if ballot is `Commit`
+    pub commitment: [u8; 32],
+
+    /// Nonce of last relayed ballot, relayed ballot must carry a greater one
+    pub relay_nonce: u64,
+}
+
+impl Default for UserVotes {
+    fn default() -> Self {
+        Self {
+            ballot: BallotKind::None,
+            choice: 0,
+            weight: 0,
+            governing_mint: [0; 32],
+            credits_spent: 0,
+            ranking: [UNRANKED; MAX_OPTIONS],
+            tallied_rounds: 0,
+            approvals: 0,
+            scores: [0; MAX_OPTIONS],
+            cast_by: [0; 32],
+            commitment: [0; 32],
+            relay_nonce: 0,
+        }
+    }
 }
 
 impl UserVotes {
+    pub fn is_voted(&self) -> bool {
+        self.ballot != BallotKind::None
+    }
+
+    pub fn is_ranked(&self) -> bool {
+        self.ranking[0] != UNRANKED
+    }
+
+    /// Forget cast ballot, keeps `governing_mint` as voter record is released separately
+    pub fn clear_ballot(&mut self) {
+        self.ballot = BallotKind::None;
+        self.choice = 0;
+        self.weight = 0;
+        self.credits_spent = 0;
+        self.ranking = [UNRANKED; MAX_OPTIONS];
+        self.approvals = 0;
+        self.scores = [0; MAX_OPTIONS];
+        self.cast_by = [0; 32];
+        self.commitment = [0; 32];
+    }
+
     pub fn get_uservote_pubkey_with_bump(user: &Pubkey, vote: &Pubkey) -> (Pubkey, u8) {
         Pubkey::find_program_address(
             &[&user.to_bytes(), &vote.to_bytes(), VOTE_SEED.as_bytes()],
@@ -20,59 +115,699 @@ impl UserVotes {
         let (pubkey, _) = Self::get_uservote_pubkey_with_bump(user, vote);
         pubkey
     }
+
+    /// Ballot of anonymous vote, derived from nullifier so it can't be derived from voter
+    pub fn get_nullifier_pubkey_with_bump(nullifier: &[u8; 32], vote: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[nullifier, &vote.to_bytes(), NULLIFIER_SEED.as_bytes()],
+            &id(),
+        )
+    }
+
+    pub fn get_nullifier_pubkey(nullifier: &[u8; 32], vote: &Pubkey) -> Pubkey {
+        let (pubkey, _) = Self::get_nullifier_pubkey_with_bump(nullifier, vote);
+        pubkey
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct VoterRecord {
+    pub voter: [u8; 32],
+
+    pub governing_mint: [u8; 32],
+
+    /// Tokens held in escrow, used as voting power
+    pub amount: u64,
+
+    /// Count of ballots not yet released by closing their `UserVotes`, blocks withdraw
+    pub active_votes: u32,
+
+    /// Unix timestamp until which deposit can't be withdrawn, zero if never locked
+    pub lockup_end: i64,
+}
+
+/// Marks voter as member of members-only vote
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct Membership {
+    pub vote: [u8; 32],
+
+    pub voter: [u8; 32],
+}
+
+impl Membership {
+    pub fn get_membership_pubkey_with_bump(vote: &Pubkey, voter: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[&vote.to_bytes(), &voter.to_bytes(), MEMBER_SEED.as_bytes()],
+            &id(),
+        )
+    }
+
+    pub fn get_membership_pubkey(vote: &Pubkey, voter: &Pubkey) -> Pubkey {
+        let (pubkey, _) = Self::get_membership_pubkey_with_bump(vote, voter);
+        pubkey
+    }
+}
+
+/// Lets delegate cast ballots for delegator in votes of realm
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct Delegation {
+    pub realm: [u8; 32],
+
+    pub delegator: [u8; 32],
+
+    pub delegate: [u8; 32],
+}
+
+impl Delegation {
+    pub fn get_delegation_pubkey_with_bump(realm: &Pubkey, delegator: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[&realm.to_bytes(), &delegator.to_bytes(), DELEGATION_SEED.as_bytes()],
+            &id(),
+        )
+    }
+
+    pub fn get_delegation_pubkey(realm: &Pubkey, delegator: &Pubkey) -> Pubkey {
+        let (pubkey, _) = Self::get_delegation_pubkey_with_bump(realm, delegator);
+        pubkey
+    }
+}
+
+/// Account of instruction run by passed vote, governance PDA signs for itself
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct ProposalAccount {
+    pub pubkey: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct ProposalInstruction {
+    pub program_id: Pubkey,
+    pub accounts: Vec<ProposalAccount>,
+    pub data: Vec<u8>,
+}
+
+impl From<&ProposalInstruction> for Instruction {
+    fn from(instruction: &ProposalInstruction) -> Self {
+        let accounts = instruction
+            .accounts
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: account.pubkey,
+                is_signer: account.is_signer,
+                is_writable: account.is_writable,
+            })
+            .collect();
+        Instruction { program_id: instruction.program_id, accounts, data: instruction.data.clone() }
+    }
+}
+
+/// Instruction attached to vote, executed once after the vote passed
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct ProposalTransaction {
+    pub vote: [u8; 32],
+
+    pub index: u16,
+
+    pub instruction: ProposalInstruction,
+
+    /// Time the instruction was executed, zero until then
+    pub executed_at: i64,
+}
+
+impl ProposalTransaction {
+    pub fn get_proposal_transaction_pubkey_with_bump(vote: &Pubkey, index: u16) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[&vote.to_bytes(), &index.to_le_bytes(), PROPOSAL_SEED.as_bytes()],
+            &id(),
+        )
+    }
+
+    pub fn get_proposal_transaction_pubkey(vote: &Pubkey, index: u16) -> Pubkey {
+        let (pubkey, _) = Self::get_proposal_transaction_pubkey_with_bump(vote, index);
+        pubkey
+    }
+
+    pub fn is_executed(&self) -> bool {
+        self.executed_at != 0
+    }
+}
+
+/// Time after timelock of passed vote its proposal transactions are expected to run,
+/// after that transaction not executed yet can be cancelled
+pub const EXECUTION_GRACE_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// Longest lockup, gives the whole `MAX_LOCKUP_BONUS`
+pub const MAX_LOCKUP_SECS: u64 = 4 * 365 * 24 * 60 * 60;
+/// Extra voting power in basis points of deposit for max remaining lockup
+pub const MAX_LOCKUP_BONUS: u64 = 30_000;
+
+impl VoterRecord {
+    /// Deposit plus bonus proportional to remaining lockup, so it decays linearly to unlock
+    pub fn voting_power(&self, unix_timestamp: i64) -> u64 {
+        let remaining = (self.lockup_end - unix_timestamp).clamp(0, MAX_LOCKUP_SECS as i64);
+        let bonus = self.amount as u128 * MAX_LOCKUP_BONUS as u128 * remaining as u128
+            / (MAX_THRESHOLD as u128 * MAX_LOCKUP_SECS as u128);
+        (self.amount as u128 + bonus).min(u64::MAX as u128) as u64
+    }
+
+    pub fn get_voter_record_pubkey_with_bump(voter: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[&voter.to_bytes(), &mint.to_bytes(), REGISTRY_SEED.as_bytes()],
+            &id(),
+        )
+    }
+
+    pub fn get_voter_record_pubkey(voter: &Pubkey, mint: &Pubkey) -> Pubkey {
+        let (pubkey, _) = Self::get_voter_record_pubkey_with_bump(voter, mint);
+        pubkey
+    }
+
+    /// Token account holding deposited tokens, owned by voter record
+    pub fn get_escrow_pubkey_with_bump(voter: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[&voter.to_bytes(), &mint.to_bytes(), ESCROW_SEED.as_bytes()],
+            &id(),
+        )
+    }
+
+    pub fn get_escrow_pubkey(voter: &Pubkey, mint: &Pubkey) -> Pubkey {
+        let (pubkey, _) = Self::get_escrow_pubkey_with_bump(voter, mint);
+        pubkey
+    }
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
 pub enum VoteStatus {
     Alive,
-    Closed,
+    Passed,
+    Rejected,
+    QuorumNotReached,
+    /// Ranked vote closed with quorum, waiting for `Tally` to find the winner
+    Tallying,
+}
+
+impl VoteStatus {
+    /// Outcome is known and will not change
+    pub fn is_final(&self) -> bool {
+        !matches!(self, VoteStatus::Alive | VoteStatus::Tallying)
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq)]
+pub enum VotingMode {
+    /// Ballot counts with its whole weight
+    Simple,
+    /// Ballot spends voice credits and counts with square root of them
+    Quadratic,
+    /// Ballot ranks poll options, winner is found by instant runoff
+    Ranked,
+    /// Ballot approves any subset of poll options, each approved once
+    Approval,
+    /// Ballot scores every poll option from zero to `max_score`
+    Score,
+}
+
+pub const MAX_OPTIONS: usize = 16;
+/// Padding of ranked ballot after its last ranked option
+pub const UNRANKED: u8 = u8::MAX;
+/// `RankedTally::winner` while there is none
+pub const NO_WINNER: u8 = u8::MAX;
+pub const MAX_OPTION_LEN: usize = 32;
+pub const MAX_THRESHOLD: u16 = 10_000;
+pub const SIMPLE_MAJORITY: u16 = 5_000;
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct VoteConfig {
+    /// Labels of poll options, empty for For/Against vote
+    pub options: Vec<String>,
+
+    /// Min total weight of ballots, abstentions included, for the vote to have an outcome.
+    /// Ballot weighs 1 unless it is weighted by allowlist, deposit or voice credits
+    pub quorum_weight: u64,
+
+    /// Share of decided ballots in basis points the leading side must exceed to pass
+    pub threshold: u16,
+
+    /// Unix timestamp before which ballots are rejected
+    pub start_ts: Option<i64>,
+
+    /// Unix timestamp after which ballots are rejected and anyone can close the vote
+    pub end_ts: Option<i64>,
+
+    /// SPL Token mint whose deposit is voting power, one vote per user if not set
+    pub governing_mint: Option<Pubkey>,
+
+    pub mode: VotingMode,
+
+    /// Voice credits per unit of voter weight in quadratic vote
+    pub voice_credits: u64,
+
+    /// Highest score of option in score vote
+    pub max_score: u8,
+
+    /// Merkle root of `allowlist_leaf` of eligible voters, anyone can vote if not set
+    pub voter_root: Option<[u8; 32]>,
+
+    /// Only voters added with `AddVoter` can vote
+    pub members_only: bool,
+
+    /// Verified Metaplex collection whose NFTs vote instead of wallets
+    pub collection: Option<Pubkey>,
+
+    /// Makes ballots secret: only commitments are accepted until `end_ts`,
+    /// then they are revealed and counted until this Unix timestamp
+    pub reveal_end_ts: Option<i64>,
+
+    /// Merkle root of voter commitments for anonymous vote, ballots are then recorded
+    /// by nullifier instead of user, see `VoteAnonymous`. Ballots are unlinkable only
+    /// with `anonymous_verifier`.
+    pub anonymous_root: Option<[u8; 32]>,
+
+    /// Program verifying zero-knowledge proofs of anonymous ballots, if any
+    pub anonymous_verifier: Option<Pubkey>,
+
+    /// Timelock between close of passed vote and execution of its proposal transactions
+    pub execution_delay_secs: u64,
+}
+
+impl Default for VoteConfig {
+    fn default() -> Self {
+        Self {
+            options: vec![],
+            quorum_weight: 0,
+            threshold: SIMPLE_MAJORITY,
+            start_ts: None,
+            end_ts: None,
+            governing_mint: None,
+            mode: VotingMode::Simple,
+            voice_credits: 0,
+            max_score: 0,
+            voter_root: None,
+            members_only: false,
+            collection: None,
+            reveal_end_ts: None,
+            anonymous_root: None,
+            anonymous_verifier: None,
+            execution_delay_secs: 0,
+        }
+    }
+}
+
+impl VoteConfig {
+    pub fn is_valid(&self) -> bool {
+        if self.mode == VotingMode::Quadratic && self.voice_credits == 0 {
+            return false;
+        }
+
+        if self.options.is_empty() {
+            return !matches!(
+                self.mode,
+                VotingMode::Ranked | VotingMode::Approval | VotingMode::Score
+            );
+        }
+
+        if self.mode == VotingMode::Score && self.max_score == 0 {
+            return false;
+        }
+
+        (2..=MAX_OPTIONS).contains(&self.options.len())
+            && self
+                .options
+                .iter()
+                .all(|option| !option.is_empty() && option.len() <= MAX_OPTION_LEN)
+    }
+}
+
+/// Instant runoff state of ranked vote, each round recounts every ranked ballot
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct RankedTally {
+    /// Count of ranked ballots cast
+    pub ballots: u64,
+
+    pub round: u8,
+
+    /// Count of ballots counted in current round
+    pub counted: u64,
+
+    /// Bitmask of eliminated options
+    pub eliminated: u16,
+
+    /// Weight of ballots per option for every round, allocated for the longest runoff
+    pub rounds: Vec<Vec<u64>>,
+
+    /// Index of winning option, `NO_WINNER` until tally ends or if it ends in a tie
+    pub winner: u8,
+}
+
+impl RankedTally {
+    pub fn new(options: usize) -> Self {
+        Self {
+            ballots: 0,
+            round: 0,
+            counted: 0,
+            eliminated: 0,
+            rounds: vec![vec![0; options]; options],
+            winner: NO_WINNER,
+        }
+    }
+
+    pub fn is_eliminated(&self, option: u8) -> bool {
+        self.eliminated & (1 << option) != 0
+    }
+
+    /// Most preferred option of ballot not yet eliminated, `None` if ballot is exhausted
+    pub fn preference(&self, ranking: &[u8]) -> Option<u8> {
+        ranking
+            .iter()
+            .copied()
+            .take_while(|&option| option != UNRANKED)
+            .find(|&option| !self.is_eliminated(option))
+    }
+
+    /// Ends current round once all ballots are counted in it. Option with majority of
+    /// not exhausted ballots wins, otherwise the last one with fewest votes is eliminated
+    /// and next round starts. Returns `true` when the tally is over.
+    pub fn close_round(&mut self) -> bool {
+        let counts = &self.rounds[self.round as usize];
+        let active: Vec<u8> =
+            (0..counts.len() as u8).filter(|&option| !self.is_eliminated(option)).collect();
+        let total: u128 = active.iter().map(|&option| counts[option as usize] as u128).sum();
+        let leader = active.iter().copied().max_by_key(|&option| counts[option as usize]);
+        let loser = active.iter().copied().rev().min_by_key(|&option| counts[option as usize]);
+
+        match (leader, loser) {
+            _ if total == 0 => true,
+            (Some(leader), _) if counts[leader as usize] as u128 * 2 > total => {
+                self.winner = leader;
+                true
+            }
+            (Some(_), Some(loser)) if active.len() > 2 => {
+                self.eliminated |= 1 << loser;
+                self.round += 1;
+                self.counted = 0;
+                false
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Leaf of voter allowlist, `weight` replaces one vote per user unless vote is token-weighted
+pub fn allowlist_leaf(voter: &Pubkey, weight: u64) -> [u8; 32] {
+    hashv(&[&[0], &voter.to_bytes(), &weight.to_le_bytes()]).to_bytes()
+}
+
+/// Parent of two allowlist nodes, hashed in sorted order so proof needs no sides
+pub fn allowlist_node(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let (first, second) = if left <= right { (left, right) } else { (right, left) };
+    hashv(&[&[1], first, second]).to_bytes()
+}
+
+/// Voter commitment of anonymous vote without zero-knowledge verifier, `voter_key` is
+/// one-off key not linked to wallet of voter. The key signs its ballots and its leaf is
+/// public, so such ballots are pseudonymous only: linkable to each other and to whoever
+/// registered the key.
+pub fn anonymous_leaf(voter_key: &Pubkey) -> [u8; 32] {
+    hashv(&[&[2], &voter_key.to_bytes()]).to_bytes()
+}
+
+/// Nullifier of `voter_key` ballot in vote, keeps the key to one ballot per vote. It hides
+/// nothing, as the key itself signs the ballot.
+pub fn anonymous_nullifier(voter_key: &Pubkey, vote: &Pubkey) -> [u8; 32] {
+    hashv(&[&[3], &voter_key.to_bytes(), &vote.to_bytes()]).to_bytes()
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Debug)]
 pub struct Vote {
     pub admin: [u8; 32],
 
-    pub all_votes_for: u32,
+    /// Vote counter of realm this vote belongs to
+    pub realm: [u8; 32],
+
+    pub all_votes_for: u64,
+
+    pub all_votes_against: u64,
+
+    /// Counted toward participation, but not toward For/Against ratio
+    pub all_votes_abstain: u64,
+
+    /// Tally per poll option, same length as `config.options`
+    pub option_votes: Vec<u64>,
 
-    pub all_votes_against: u32,
+    /// Weight of approval and score ballots, each counted once however many options it marks,
+    /// so mean score of option is its `option_votes` divided by this
+    pub all_ballots: u64,
 
     pub clock: u64,
 
+    /// Time the vote was created
+    pub created_at: i64,
+
     pub status: VoteStatus,
+
+    /// Who closed the vote, zeroes while it is alive
+    pub closed_by: [u8; 32],
+
+    /// Time the vote was closed, zero while it is alive
+    pub closed_at: i64,
+
+    pub config: VoteConfig,
+
+    /// Instant runoff state, set only for ranked vote
+    pub ranked_tally: Option<RankedTally>,
+
+    /// Count of committed ballots not revealed yet
+    pub unrevealed: u64,
+
+    /// Count of proposal transactions attached and not closed yet, vote can't be closed
+    /// before them so passed proposal can't be dropped
+    pub proposal_transactions: u16,
 }
 
 impl Vote {
-    pub fn get_vote_pubkey_with_bump(vote_seed: &Pubkey) -> (Pubkey, u8) {
-        Pubkey::find_program_address(&[&vote_seed.to_bytes()], &id())
+    pub fn get_vote_pubkey_with_bump(realm: &Pubkey, vote_seed: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[&realm.to_bytes(), &vote_seed.to_bytes()], &id())
     }
 
-    pub fn get_vote_pubkey(vote_seed: &Pubkey) -> Pubkey {
-        let (pubkey, _) = Self::get_vote_pubkey_with_bump(vote_seed);
+    pub fn get_vote_pubkey(realm: &Pubkey, vote_seed: &Pubkey) -> Pubkey {
+        let (pubkey, _) = Self::get_vote_pubkey_with_bump(realm, vote_seed);
         pubkey
     }
 
-    pub fn new(admin: [u8; 32], clock: u64) -> Self {
-        Self { admin, all_votes_for: 0, all_votes_against: 0, clock, status: VoteStatus::Alive }
+    pub fn new(
+        admin: [u8; 32],
+        realm: [u8; 32],
+        clock: u64,
+        created_at: i64,
+        config: VoteConfig,
+    ) -> Self {
+        Self {
+            admin,
+            realm,
+            all_votes_for: 0,
+            all_votes_against: 0,
+            all_votes_abstain: 0,
+            option_votes: vec![0; config.options.len()],
+            all_ballots: 0,
+            clock,
+            created_at,
+            status: VoteStatus::Alive,
+            closed_by: [0; 32],
+            closed_at: 0,
+            ranked_tally: match config.mode {
+                VotingMode::Ranked => Some(RankedTally::new(config.options.len())),
+                _ => None,
+            },
+            unrevealed: 0,
+            proposal_transactions: 0,
+            config,
+        }
+    }
+
+    pub fn is_poll(&self) -> bool {
+        !self.config.options.is_empty()
+    }
+
+    pub fn is_started(&self, unix_timestamp: i64) -> bool {
+        !matches!(self.config.start_ts, Some(start_ts) if unix_timestamp < start_ts)
+    }
+
+    pub fn is_ended(&self, unix_timestamp: i64) -> bool {
+        matches!(self.config.end_ts, Some(end_ts) if unix_timestamp >= end_ts)
+    }
+
+    pub fn is_commit_reveal(&self) -> bool {
+        self.config.reveal_end_ts.is_some()
+    }
+
+    pub fn is_revealing(&self, unix_timestamp: i64) -> bool {
+        self.is_ended(unix_timestamp) && !self.is_over(unix_timestamp)
+    }
+
+    /// Ballots can't be cast or revealed anymore
+    pub fn is_over(&self, unix_timestamp: i64) -> bool {
+        match self.config.reveal_end_ts {
+            Some(reveal_end_ts) => unix_timestamp >= reveal_end_ts,
+            None => self.is_ended(unix_timestamp),
+        }
+    }
+
+    /// Vote lived at least `time_to_live` slots and its end time, if any, passed
+    pub fn is_expired(&self, clock: &Clock, time_to_live: u64) -> bool {
+        let is_lived = clock.slot - self.clock >= time_to_live;
+        is_lived && (self.config.end_ts.is_none() || self.is_over(clock.unix_timestamp))
+    }
+
+    /// Vote lived longer than realm max lifetime, so anyone can finalize it
+    pub fn is_lifetime_elapsed(&self, unix_timestamp: i64, max_lifetime_secs: u64) -> bool {
+        let lifetime = i64::try_from(max_lifetime_secs).unwrap_or(i64::MAX);
+        unix_timestamp >= self.created_at.saturating_add(lifetime)
+    }
+
+    /// Passed vote executes proposal transactions once execution delay after its close elapsed
+    pub fn is_executable(&self, unix_timestamp: i64) -> bool {
+        let delay = i64::try_from(self.config.execution_delay_secs).unwrap_or(i64::MAX);
+        self.status == VoteStatus::Passed && unix_timestamp >= self.closed_at.saturating_add(delay)
+    }
+
+    /// Grace period of passed vote elapsed, so its not executed transactions can be cancelled
+    pub fn is_execution_expired(&self, unix_timestamp: i64) -> bool {
+        let delay = self.config.execution_delay_secs.saturating_add(EXECUTION_GRACE_SECS);
+        let delay = i64::try_from(delay).unwrap_or(i64::MAX);
+        self.status == VoteStatus::Passed && unix_timestamp >= self.closed_at.saturating_add(delay)
+    }
+
+    /// Any ballot was cast, including committed and ranked ones
+    pub fn has_ballots(&self) -> bool {
+        self.total_votes() > 0
+            || self.unrevealed > 0
+            || matches!(&self.ranked_tally, Some(tally) if tally.ballots > 0)
+    }
+
+    /// Weight of all counted ballots, saturates at `u64::MAX`
+    pub fn total_votes(&self) -> u64 {
+        let option_votes = match self.config.mode {
+            VotingMode::Approval | VotingMode::Score => self.all_ballots as u128,
+            _ => self.option_votes.iter().map(|&votes| votes as u128).sum(),
+        };
+        let total = self.all_votes_for as u128
+            + self.all_votes_against as u128
+            + self.all_votes_abstain as u128
+            + option_votes;
+        total.min(u64::MAX as u128) as u64
+    }
+
+    /// Index of poll option with the most votes, `None` on tie or for For/Against vote
+    pub fn leading_option(&self) -> Option<u8> {
+        let max = *self.option_votes.iter().max()?;
+        let mut leaders = self.option_votes.iter().enumerate().filter(|(_, &votes)| votes == max);
+        match (leaders.next(), leaders.next()) {
+            (Some((index, _)), None) => Some(index as u8),
+            _ => None,
+        }
+    }
+
+    pub fn outcome(&self) -> VoteStatus {
+        if self.total_votes() < self.config.quorum_weight {
+            return VoteStatus::QuorumNotReached;
+        }
+
+        if self.config.mode == VotingMode::Ranked {
+            return VoteStatus::Tallying;
+        }
+
+        // summed in u128, so boosted weights can't overflow
+        let (leading, decided) = if self.is_poll() {
+            let leading = match self.leading_option() {
+                Some(index) => self.option_votes[index as usize],
+                None => return VoteStatus::Rejected,
+            };
+            let decided = match self.config.mode {
+                VotingMode::Approval => self.all_ballots as u128,
+                VotingMode::Score => self.all_ballots as u128 * self.config.max_score as u128,
+                _ => self.option_votes.iter().map(|&votes| votes as u128).sum(),
+            };
+            (leading, decided)
+        } else {
+            (self.all_votes_for, self.all_votes_for as u128 + self.all_votes_against as u128)
+        };
+
+        let leading_share = leading as u128 * MAX_THRESHOLD as u128;
+        if leading_share > decided * self.config.threshold as u128 {
+            VoteStatus::Passed
+        } else {
+            VoteStatus::Rejected
+        }
+    }
+}
+
+pub const MAX_REALM_NAME_LEN: usize = 32;
+pub const DEFAULT_MAX_VOTES: u8 = 10;
+pub const DEFAULT_TIME_TO_LIVE: u64 = 10;
+pub const DEFAULT_MAX_LIFETIME_SECS: u64 = 30 * 24 * 60 * 60;
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct Settings {
+    /// Max count of votes that can be alive at the same time
+    pub max_votes: u8,
+
+    /// Min count of slots a vote lives before it can be closed
+    pub time_to_live: u64,
+
+    /// Seconds after creation a vote without end time can be finalized by admin only,
+    /// after that anyone can finalize it
+    pub max_lifetime_secs: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            max_votes: DEFAULT_MAX_VOTES,
+            time_to_live: DEFAULT_TIME_TO_LIVE,
+            max_lifetime_secs: DEFAULT_MAX_LIFETIME_SECS,
+        }
     }
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Debug)]
 pub struct VoteCounter {
     pub counter: u8,
+
+    pub authority: [u8; 32],
+
+    /// Realm name the PDA is derived from
+    pub name: String,
+
+    pub settings: Settings,
 }
 
 impl VoteCounter {
-    pub fn get_vote_pubkey_with_bump() -> (Pubkey, u8) {
-        Pubkey::find_program_address(&[SETTINGS_SEED.as_bytes()], &id())
+    pub fn get_vote_pubkey_with_bump(realm: &str) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[SETTINGS_SEED.as_bytes(), realm.as_bytes()], &id())
     }
 
-    pub fn get_vote_pubkey() -> Pubkey {
-        let (pubkey, _) = Self::get_vote_pubkey_with_bump();
+    pub fn get_vote_pubkey(realm: &str) -> Pubkey {
+        let (pubkey, _) = Self::get_vote_pubkey_with_bump(realm);
         pubkey
     }
 
-    pub fn is_ok_vote_pubkey(vote_pubkey: &Pubkey) -> bool {
-        let (pubkey, _) = Self::get_vote_pubkey_with_bump();
+    pub fn is_ok_vote_pubkey(realm: &str, vote_pubkey: &Pubkey) -> bool {
+        let (pubkey, _) = Self::get_vote_pubkey_with_bump(realm);
         pubkey.to_bytes() == vote_pubkey.to_bytes()
     }
+
+    pub fn is_ok_name(realm: &str) -> bool {
+        !realm.is_empty() && realm.len() <= MAX_REALM_NAME_LEN
+    }
+
+    /// Program-owned signer of proposal transactions of realm votes, can hold realm assets
+    pub fn get_governance_pubkey_with_bump(realm: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[&realm.to_bytes(), GOVERNANCE_SEED.as_bytes()], &id())
+    }
+
+    pub fn get_governance_pubkey(realm: &Pubkey) -> Pubkey {
+        let (pubkey, _) = Self::get_governance_pubkey_with_bump(realm);
+        pubkey
+    }
 }
@@ -1,30 +1,37 @@
 #![cfg(feature = "test-bpf")]
 use std::assert_eq;
 
-use borsh::{BorshDeserialize};
-use solana_program::{
-    pubkey::Pubkey,
-    system_instruction,
-};
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{clock::Clock, program_pack::Pack, pubkey::Pubkey, system_instruction};
 use solana_program_test::{
     processor,
-    tokio::{
-        self,
-    },
+    tokio::{self},
     ProgramTest, ProgramTestContext,
 };
 
+use solana_sdk::account::Account;
+use solana_sdk::instruction::InstructionError;
 use solana_sdk::signature::{Keypair, Signer};
-use solana_sdk::transaction::Transaction;
-use voting::state::{UserVotes, Vote, VoteCounter, VoteStatus};
+use solana_sdk::transaction::{Transaction, TransactionError};
+use voting::state::{
+    allowlist_leaf, allowlist_node, anonymous_leaf, anonymous_nullifier, BallotKind, Membership,
+    ProposalAccount, ProposalInstruction, ProposalTransaction, Settings, UserVotes, Vote,
+    VoteConfig, VoteCounter, VoteStatus, VoterRecord, VotingMode, DEFAULT_MAX_LIFETIME_SECS,
+    EXECUTION_GRACE_SECS, MAX_LOCKUP_SECS,
+};
 use voting::{
     entrypoint::process_instruction,
+    error::{VoteError, BATCH_ENTRY_ERROR},
     id,
-    instruction::{Direction, VoteInstruction},
+    instruction::{Direction, VoteInstruction, VoterProof},
+    metadata::{metadata_program, Collection, Data, Metadata},
 };
 
+const REALM: &str = "dao";
+
 struct Env {
     ctx: ProgramTestContext,
+    realm: Pubkey,
     admin: Keypair,
     user_01: Keypair,
     user_02: Keypair,
@@ -33,7 +40,12 @@ struct Env {
 
 impl Env {
     async fn new() -> Self {
-        let program_test = ProgramTest::new("voting", id(), processor!(process_instruction));
+        let mut program_test = ProgramTest::new("voting", id(), processor!(process_instruction));
+        program_test.add_program(
+            "spl_token",
+            spl_token::id(),
+            processor!(spl_token::processor::Processor::process),
+        );
         let mut ctx = program_test.start_with_context().await;
 
         let admin = Keypair::new();
@@ -73,19 +85,127 @@ impl Env {
             .unwrap();
 
         let tx = Transaction::new_signed_with_payer(
-            &[VoteInstruction::create_vote_counter(&admin.pubkey())],
+            &[VoteInstruction::create_realm(&admin.pubkey(), REALM, Settings::default())],
             Some(&admin.pubkey()),
             &[&admin],
             ctx.last_blockhash,
         );
         ctx.banks_client.process_transaction(tx).await.unwrap();
 
-        let acc =
-            ctx.banks_client.get_account(VoteCounter::get_vote_pubkey()).await.unwrap().unwrap();
+        let realm = VoteCounter::get_vote_pubkey(REALM);
+        let acc = ctx.banks_client.get_account(realm).await.unwrap().unwrap();
         let vote_counter = VoteCounter::try_from_slice(acc.data.as_slice()).unwrap();
         assert_eq!(vote_counter.counter, 0);
 
-        Env { ctx, admin, user_01, user_02, user_03 }
+        Env { ctx, realm, admin, user_01, user_02, user_03 }
+    }
+
+    // moves clock past max lifetime of vote, so anyone can finalize it
+    async fn warp_past_lifetime(&mut self, slot: u64) {
+        self.ctx.warp_to_slot(slot).unwrap();
+        let mut clock = self.ctx.banks_client.get_sysvar::<Clock>().await.unwrap();
+        clock.unix_timestamp += DEFAULT_MAX_LIFETIME_SECS as i64;
+        self.ctx.set_sysvar(&clock);
+    }
+
+    async fn create_mint(&mut self) -> Pubkey {
+        let mint = Keypair::new();
+        let rent = self.ctx.banks_client.get_rent().await.unwrap();
+        let tx = Transaction::new_signed_with_payer(
+            &[
+                system_instruction::create_account(
+                    &self.ctx.payer.pubkey(),
+                    &mint.pubkey(),
+                    rent.minimum_balance(spl_token::state::Mint::LEN),
+                    spl_token::state::Mint::LEN as u64,
+                    &spl_token::id(),
+                ),
+                spl_token::instruction::initialize_mint(
+                    &spl_token::id(),
+                    &mint.pubkey(),
+                    &self.admin.pubkey(),
+                    None,
+                    0,
+                )
+                .unwrap(),
+            ],
+            Some(&self.ctx.payer.pubkey()),
+            &[&self.ctx.payer, &mint],
+            self.ctx.last_blockhash,
+        );
+        self.ctx.banks_client.process_transaction(tx).await.unwrap();
+        mint.pubkey()
+    }
+
+    async fn create_token_account(&mut self, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Pubkey {
+        let account = Keypair::new();
+        let rent = self.ctx.banks_client.get_rent().await.unwrap();
+        let tx = Transaction::new_signed_with_payer(
+            &[
+                system_instruction::create_account(
+                    &self.ctx.payer.pubkey(),
+                    &account.pubkey(),
+                    rent.minimum_balance(spl_token::state::Account::LEN),
+                    spl_token::state::Account::LEN as u64,
+                    &spl_token::id(),
+                ),
+                spl_token::instruction::initialize_account(
+                    &spl_token::id(),
+                    &account.pubkey(),
+                    mint,
+                    owner,
+                )
+                .unwrap(),
+                spl_token::instruction::mint_to(
+                    &spl_token::id(),
+                    mint,
+                    &account.pubkey(),
+                    &self.admin.pubkey(),
+                    &[],
+                    amount,
+                )
+                .unwrap(),
+            ],
+            Some(&self.ctx.payer.pubkey()),
+            &[&self.ctx.payer, &account, &self.admin],
+            self.ctx.last_blockhash,
+        );
+        self.ctx.banks_client.process_transaction(tx).await.unwrap();
+        account.pubkey()
+    }
+
+    /// Mint NFT to `owner` with metadata pointing to `collection`
+    async fn create_nft(&mut self, owner: &Pubkey, collection: Collection) -> (Pubkey, Pubkey) {
+        let mint = self.create_mint().await;
+        let token_account = self.create_token_account(&mint, owner, 1).await;
+        let metadata = Metadata {
+            key: 4,
+            update_authority: self.admin.pubkey(),
+            mint,
+            data: Data {
+                name: "Member".to_string(),
+                symbol: "DAO".to_string(),
+                uri: String::new(),
+                seller_fee_basis_points: 0,
+                creators: None,
+            },
+            primary_sale_happened: false,
+            is_mutable: true,
+            edition_nonce: None,
+            token_standard: None,
+            collection: Some(collection),
+        };
+        let mut data = metadata.try_to_vec().unwrap();
+        data.resize(679, 0);
+        let account = Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: metadata_program::id(),
+            executable: false,
+            rent_epoch: 0,
+        };
+        self.ctx.set_account(&Metadata::get_metadata_pubkey(&mint), &account.into());
+        (mint, token_account)
     }
 }
 
@@ -95,7 +215,12 @@ async fn test_vote() {
     let mut env = Env::new().await;
     let vote_seed = Pubkey::new_unique();
     let tx = Transaction::new_signed_with_payer(
-        &[VoteInstruction::create_vote(&env.admin.pubkey(), &vote_seed)],
+        &[VoteInstruction::create_vote(
+            &env.admin.pubkey(),
+            &env.realm,
+            &vote_seed,
+            VoteConfig::default(),
+        )],
         Some(&env.admin.pubkey()),
         &[&env.admin],
         env.ctx.last_blockhash,
@@ -106,7 +231,7 @@ async fn test_vote() {
     let tx = Transaction::new_signed_with_payer(
         &[VoteInstruction::vote(
             &env.user_01.pubkey(),
-            &Vote::get_vote_pubkey(&vote_seed),
+            &Vote::get_vote_pubkey(&env.realm, &vote_seed),
             Direction::For,
         )],
         Some(&env.user_01.pubkey()),
@@ -120,19 +245,24 @@ async fn test_vote() {
         .banks_client
         .get_account(UserVotes::get_uservote_pubkey(
             &env.user_01.pubkey(),
-            &Vote::get_vote_pubkey(&vote_seed),
+            &Vote::get_vote_pubkey(&env.realm, &vote_seed),
         ))
         .await
         .unwrap()
         .unwrap();
     let user_votes = UserVotes::try_from_slice(&acc.data).unwrap();
 
-    let acc =
-        env.ctx.banks_client.get_account(Vote::get_vote_pubkey(&vote_seed)).await.unwrap().unwrap();
+    let acc = env
+        .ctx
+        .banks_client
+        .get_account(Vote::get_vote_pubkey(&env.realm, &vote_seed))
+        .await
+        .unwrap()
+        .unwrap();
 
     let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
     assert_eq!(vote.all_votes_for, 1);
-    assert_eq!(user_votes.is_voted, true);
+    assert!(user_votes.is_voted());
 }
 
 // test of 3 users vote
@@ -141,7 +271,12 @@ async fn test_vote_third() {
     let mut env = Env::new().await;
     let vote_seed = Pubkey::new_unique();
     let tx = Transaction::new_signed_with_payer(
-        &[VoteInstruction::create_vote(&env.admin.pubkey(), &vote_seed)],
+        &[VoteInstruction::create_vote(
+            &env.admin.pubkey(),
+            &env.realm,
+            &vote_seed,
+            VoteConfig::default(),
+        )],
         Some(&env.admin.pubkey()),
         &[&env.admin],
         env.ctx.last_blockhash,
@@ -152,7 +287,7 @@ async fn test_vote_third() {
     let tx = Transaction::new_signed_with_payer(
         &[VoteInstruction::vote(
             &env.user_01.pubkey(),
-            &Vote::get_vote_pubkey(&vote_seed),
+            &Vote::get_vote_pubkey(&env.realm, &vote_seed),
             Direction::For,
         )],
         Some(&env.user_01.pubkey()),
@@ -164,7 +299,7 @@ async fn test_vote_third() {
     let tx = Transaction::new_signed_with_payer(
         &[VoteInstruction::vote(
             &env.user_02.pubkey(),
-            &Vote::get_vote_pubkey(&vote_seed),
+            &Vote::get_vote_pubkey(&env.realm, &vote_seed),
             Direction::For,
         )],
         Some(&env.user_02.pubkey()),
@@ -176,7 +311,7 @@ async fn test_vote_third() {
     let tx = Transaction::new_signed_with_payer(
         &[VoteInstruction::vote(
             &env.user_03.pubkey(),
-            &Vote::get_vote_pubkey(&vote_seed),
+            &Vote::get_vote_pubkey(&env.realm, &vote_seed),
             Direction::Against,
         )],
         Some(&env.user_03.pubkey()),
@@ -185,14 +320,73 @@ async fn test_vote_third() {
     );
     env.ctx.banks_client.process_transaction(tx).await.unwrap();
 
-    let acc =
-        env.ctx.banks_client.get_account(Vote::get_vote_pubkey(&vote_seed)).await.unwrap().unwrap();
+    let acc = env
+        .ctx
+        .banks_client
+        .get_account(Vote::get_vote_pubkey(&env.realm, &vote_seed))
+        .await
+        .unwrap()
+        .unwrap();
 
     let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
     assert_eq!(vote.all_votes_for, 2);
     assert_eq!(vote.all_votes_against, 1);
 }
 
+// test abstain is counted and can be changed to other direction
+#[tokio::test]
+async fn test_abstain() {
+    let mut env = Env::new().await;
+    let vote_seed = Pubkey::new_unique();
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::create_vote(
+            &env.admin.pubkey(),
+            &env.realm,
+            &vote_seed,
+            VoteConfig::default(),
+        )],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote(
+            &env.user_01.pubkey(),
+            &Vote::get_vote_pubkey(&env.realm, &vote_seed),
+            Direction::Abstain,
+        )],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote(
+            &env.user_01.pubkey(),
+            &Vote::get_vote_pubkey(&env.realm, &vote_seed),
+            Direction::For,
+        )],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let acc = env
+        .ctx
+        .banks_client
+        .get_account(Vote::get_vote_pubkey(&env.realm, &vote_seed))
+        .await
+        .unwrap()
+        .unwrap();
+    let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote.all_votes_abstain, 0);
+    assert_eq!(vote.all_votes_for, 1);
+}
+
 // test delete with time wait
 #[tokio::test]
 async fn test_delete() {
@@ -201,7 +395,12 @@ async fn test_delete() {
     let vote_seed = Pubkey::new_unique();
 
     let tx = Transaction::new_signed_with_payer(
-        &[VoteInstruction::create_vote(&env.admin.pubkey(), &vote_seed)],
+        &[VoteInstruction::create_vote(
+            &env.admin.pubkey(),
+            &env.realm,
+            &vote_seed,
+            VoteConfig::default(),
+        )],
         Some(&env.admin.pubkey()),
         &[&env.admin],
         env.ctx.last_blockhash,
@@ -209,10 +408,14 @@ async fn test_delete() {
 
     env.ctx.banks_client.process_transaction(tx).await.unwrap();
 
-    env.ctx.warp_to_slot(11);
+    env.ctx.warp_to_slot(11).unwrap();
 
     let tx = Transaction::new_signed_with_payer(
-        &[VoteInstruction::delete(&env.admin.pubkey(), &Vote::get_vote_pubkey(&vote_seed))],
+        &[VoteInstruction::delete(
+            &env.admin.pubkey(),
+            &env.realm,
+            &Vote::get_vote_pubkey(&env.realm, &vote_seed),
+        )],
         Some(&env.admin.pubkey()),
         &[&env.admin],
         env.ctx.last_blockhash,
@@ -220,118 +423,401 @@ async fn test_delete() {
 
     env.ctx.banks_client.process_transaction(tx).await.unwrap();
 
-    let acc =
-        env.ctx.banks_client.get_account(Vote::get_vote_pubkey(&vote_seed)).await.unwrap().unwrap();
+    let acc = env
+        .ctx
+        .banks_client
+        .get_account(Vote::get_vote_pubkey(&env.realm, &vote_seed))
+        .await
+        .unwrap()
+        .unwrap();
     let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
 
-    assert_eq!(vote.status, VoteStatus::Closed);
+    assert_eq!(vote.status, VoteStatus::Rejected);
 }
 
-// test delete without time wait
+// test outcome of vote with quorum and supermajority threshold
 #[tokio::test]
-async fn test_delete_no_wait() {
+async fn test_outcome() {
     let mut env = Env::new().await;
 
-    let vote_seed = Pubkey::new_unique();
+    let passed_seed = Pubkey::new_unique();
+    let no_quorum_seed = Pubkey::new_unique();
+    let config = VoteConfig { quorum_weight: 3, threshold: 6_000, ..VoteConfig::default() };
 
     let tx = Transaction::new_signed_with_payer(
-        &[VoteInstruction::create_vote(&env.admin.pubkey(), &vote_seed)],
+        &[
+            VoteInstruction::create_vote(
+                &env.admin.pubkey(),
+                &env.realm,
+                &passed_seed,
+                config.clone(),
+            ),
+            VoteInstruction::create_vote(&env.admin.pubkey(), &env.realm, &no_quorum_seed, config),
+        ],
         Some(&env.admin.pubkey()),
         &[&env.admin],
         env.ctx.last_blockhash,
     );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    for (user, direction) in [
+        (&env.user_01, Direction::For),
+        (&env.user_02, Direction::For),
+        (&env.user_03, Direction::Abstain),
+    ] {
+        let tx = Transaction::new_signed_with_payer(
+            &[VoteInstruction::vote(
+                &user.pubkey(),
+                &Vote::get_vote_pubkey(&env.realm, &passed_seed),
+                direction,
+            )],
+            Some(&user.pubkey()),
+            &[user],
+            env.ctx.last_blockhash,
+        );
+        env.ctx.banks_client.process_transaction(tx).await.unwrap();
+    }
 
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote(
+            &env.user_01.pubkey(),
+            &Vote::get_vote_pubkey(&env.realm, &no_quorum_seed),
+            Direction::For,
+        )],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
     env.ctx.banks_client.process_transaction(tx).await.unwrap();
 
+    env.ctx.warp_to_slot(11).unwrap();
+
     let tx = Transaction::new_signed_with_payer(
-        &[VoteInstruction::delete(&env.admin.pubkey(), &Vote::get_vote_pubkey(&vote_seed))],
+        &[
+            VoteInstruction::delete(
+                &env.admin.pubkey(),
+                &env.realm,
+                &Vote::get_vote_pubkey(&env.realm, &passed_seed),
+            ),
+            VoteInstruction::delete(
+                &env.admin.pubkey(),
+                &env.realm,
+                &Vote::get_vote_pubkey(&env.realm, &no_quorum_seed),
+            ),
+        ],
         Some(&env.admin.pubkey()),
         &[&env.admin],
         env.ctx.last_blockhash,
     );
-
     env.ctx.banks_client.process_transaction(tx).await.unwrap();
 
-    let acc =
-        env.ctx.banks_client.get_account(Vote::get_vote_pubkey(&vote_seed)).await.unwrap().unwrap();
+    let acc = env
+        .ctx
+        .banks_client
+        .get_account(Vote::get_vote_pubkey(&env.realm, &passed_seed))
+        .await
+        .unwrap()
+        .unwrap();
     let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote.status, VoteStatus::Passed);
 
-    assert_eq!(vote.status, VoteStatus::Alive);
+    let acc = env
+        .ctx
+        .banks_client
+        .get_account(Vote::get_vote_pubkey(&env.realm, &no_quorum_seed))
+        .await
+        .unwrap()
+        .unwrap();
+    let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote.status, VoteStatus::QuorumNotReached);
 }
 
-// test user to double participate in 1 vote
-#[should_panic]
+// test of closing vote and user participation to reclaim rent
 #[tokio::test]
-async fn double_vote() {
+async fn test_close_vote() {
     let mut env = Env::new().await;
     let vote_seed = Pubkey::new_unique();
+    let vote_pubkey = Vote::get_vote_pubkey(&env.realm, &vote_seed);
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::create_vote(
+            &env.admin.pubkey(),
+            &env.realm,
+            &vote_seed,
+            VoteConfig::default(),
+        )],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote(&env.user_01.pubkey(), &vote_pubkey, Direction::For)],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::close_vote(&env.admin.pubkey(), &vote_pubkey, &env.admin.pubkey())],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    env.ctx.warp_to_slot(11).unwrap();
+
+    let recipient = Pubkey::new_unique();
     let tx = Transaction::new_signed_with_payer(
-        &[VoteInstruction::create_vote(&env.admin.pubkey(), &vote_seed)],
+        &[
+            VoteInstruction::delete(&env.admin.pubkey(), &env.realm, &vote_pubkey),
+            VoteInstruction::close_vote(&env.admin.pubkey(), &vote_pubkey, &recipient),
+        ],
         Some(&env.admin.pubkey()),
         &[&env.admin],
         env.ctx.last_blockhash,
     );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tombstone = env.ctx.banks_client.get_account(vote_pubkey).await.unwrap().unwrap();
+    assert!(tombstone.data.is_empty());
+    assert!(env.ctx.banks_client.get_balance(recipient).await.unwrap() > 0);
+
+    // closed vote can't be created again, so stale ballots can't leak into new vote
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::create_vote(
+            &env.admin.pubkey(),
+            &env.realm,
+            &vote_seed,
+            VoteConfig::default(),
+        )],
+        Some(&env.user_02.pubkey()),
+        &[&env.user_02, &env.admin],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::close_user_vote(&env.user_01.pubkey(), &vote_pubkey)],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
 
+    let user_votes_pubkey = UserVotes::get_uservote_pubkey(&env.user_01.pubkey(), &vote_pubkey);
+    assert!(env.ctx.banks_client.get_account(user_votes_pubkey).await.unwrap().is_none());
+}
+
+// test of voting window and permissionless delete after it ends
+#[tokio::test]
+async fn test_vote_window() {
+    let mut env = Env::new().await;
+    let now = env.ctx.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp;
+
+    let not_started_seed = Pubkey::new_unique();
+    let ending_seed = Pubkey::new_unique();
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::create_vote(
+                &env.admin.pubkey(),
+                &env.realm,
+                &not_started_seed,
+                VoteConfig { start_ts: Some(now + 100), ..VoteConfig::default() },
+            ),
+            VoteInstruction::create_vote(
+                &env.admin.pubkey(),
+                &env.realm,
+                &ending_seed,
+                VoteConfig { end_ts: Some(now + 100), ..VoteConfig::default() },
+            ),
+        ],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
     env.ctx.banks_client.process_transaction(tx).await.unwrap();
 
     let tx = Transaction::new_signed_with_payer(
         &[VoteInstruction::vote(
             &env.user_01.pubkey(),
-            &Vote::get_vote_pubkey(&vote_seed),
+            &Vote::get_vote_pubkey(&env.realm, &not_started_seed),
             Direction::For,
         )],
         Some(&env.user_01.pubkey()),
         &[&env.user_01],
         env.ctx.last_blockhash,
     );
-    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
 
     let tx = Transaction::new_signed_with_payer(
         &[VoteInstruction::vote(
             &env.user_01.pubkey(),
-            &Vote::get_vote_pubkey(&vote_seed),
-            Direction::Against,
+            &Vote::get_vote_pubkey(&env.realm, &ending_seed),
+            Direction::For,
         )],
         Some(&env.user_01.pubkey()),
         &[&env.user_01],
         env.ctx.last_blockhash,
     );
     env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::delete(
+            &env.user_03.pubkey(),
+            &env.realm,
+            &Vote::get_vote_pubkey(&env.realm, &ending_seed),
+        )],
+        Some(&env.user_03.pubkey()),
+        &[&env.user_03],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    env.ctx.warp_to_slot(11).unwrap();
+    let mut clock = env.ctx.banks_client.get_sysvar::<Clock>().await.unwrap();
+    clock.unix_timestamp = now + 200;
+    env.ctx.set_sysvar(&clock);
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote(
+            &env.user_02.pubkey(),
+            &Vote::get_vote_pubkey(&env.realm, &ending_seed),
+            Direction::Against,
+        )],
+        Some(&env.user_02.pubkey()),
+        &[&env.user_02],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::delete(
+            &env.user_03.pubkey(),
+            &env.realm,
+            &Vote::get_vote_pubkey(&env.realm, &ending_seed),
+        )],
+        Some(&env.user_03.pubkey()),
+        &[&env.user_03],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let acc = env
+        .ctx
+        .banks_client
+        .get_account(Vote::get_vote_pubkey(&env.realm, &ending_seed))
+        .await
+        .unwrap()
+        .unwrap();
+    let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote.status, VoteStatus::Passed);
 }
 
-// test user (not admin) try to delete vote
-#[should_panic]
+// test finalize by anyone after vote lifetime elapsed
 #[tokio::test]
-async fn not_admin() {
+async fn test_finalize() {
     let mut env = Env::new().await;
     let vote_seed = Pubkey::new_unique();
     let tx = Transaction::new_signed_with_payer(
-        &[VoteInstruction::create_vote(&env.admin.pubkey(), &vote_seed)],
+        &[VoteInstruction::create_vote(
+            &env.admin.pubkey(),
+            &env.realm,
+            &vote_seed,
+            VoteConfig::default(),
+        )],
         Some(&env.admin.pubkey()),
         &[&env.admin],
         env.ctx.last_blockhash,
     );
-
     env.ctx.banks_client.process_transaction(tx).await.unwrap();
 
     let tx = Transaction::new_signed_with_payer(
-        &[VoteInstruction::delete(&env.user_01.pubkey(), &Vote::get_vote_pubkey(&vote_seed))],
+        &[VoteInstruction::finalize(
+            &env.user_01.pubkey(),
+            &env.realm,
+            &Vote::get_vote_pubkey(&env.realm, &vote_seed),
+        )],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    env.ctx.warp_to_slot(11).unwrap();
+
+    // vote without end time can be finalized by admin only until its max lifetime elapsed
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::finalize(
+            &env.user_01.pubkey(),
+            &env.realm,
+            &Vote::get_vote_pubkey(&env.realm, &vote_seed),
+        )],
         Some(&env.user_01.pubkey()),
         &[&env.user_01],
         env.ctx.last_blockhash,
     );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    env.warp_past_lifetime(12).await;
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::finalize(
+            &env.user_01.pubkey(),
+            &env.realm,
+            &Vote::get_vote_pubkey(&env.realm, &vote_seed),
+        )],
+        Some(&env.user_02.pubkey()),
+        &[&env.user_02, &env.user_01],
+        env.ctx.last_blockhash,
+    );
     env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let acc = env
+        .ctx
+        .banks_client
+        .get_account(Vote::get_vote_pubkey(&env.realm, &vote_seed))
+        .await
+        .unwrap()
+        .unwrap();
+    let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote.status, VoteStatus::Rejected);
+    assert_eq!(vote.closed_by, env.user_01.pubkey().to_bytes());
+
+    let acc = env.ctx.banks_client.get_account(env.realm).await.unwrap().unwrap();
+    let vote_counter = VoteCounter::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote_counter.counter, 0);
 }
 
-// test of vote create
+// test delete without time wait
 #[tokio::test]
-async fn test_create_vote() {
+async fn test_delete_no_wait() {
     let mut env = Env::new().await;
 
     let vote_seed = Pubkey::new_unique();
 
     let tx = Transaction::new_signed_with_payer(
-        &[VoteInstruction::create_vote(&env.admin.pubkey(), &vote_seed)],
+        &[VoteInstruction::create_vote(
+            &env.admin.pubkey(),
+            &env.realm,
+            &vote_seed,
+            VoteConfig::default(),
+        )],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::delete(
+            &env.admin.pubkey(),
+            &env.realm,
+            &Vote::get_vote_pubkey(&env.realm, &vote_seed),
+        )],
         Some(&env.admin.pubkey()),
         &[&env.admin],
         env.ctx.last_blockhash,
@@ -339,8 +825,2146 @@ async fn test_create_vote() {
 
     env.ctx.banks_client.process_transaction(tx).await.unwrap();
 
-    let acc =
-        env.ctx.banks_client.get_account(Vote::get_vote_pubkey(&vote_seed)).await.unwrap().unwrap();
+    let acc = env
+        .ctx
+        .banks_client
+        .get_account(Vote::get_vote_pubkey(&env.realm, &vote_seed))
+        .await
+        .unwrap()
+        .unwrap();
     let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
-    assert_eq!(vote.admin, env.admin.pubkey().to_bytes());
+
+    assert_eq!(vote.status, VoteStatus::Alive);
+}
+
+// test user to change ballot in alive vote
+#[tokio::test]
+async fn double_vote() {
+    let mut env = Env::new().await;
+    let vote_seed = Pubkey::new_unique();
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::create_vote(
+            &env.admin.pubkey(),
+            &env.realm,
+            &vote_seed,
+            VoteConfig::default(),
+        )],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote(
+            &env.user_01.pubkey(),
+            &Vote::get_vote_pubkey(&env.realm, &vote_seed),
+            Direction::For,
+        )],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote(
+            &env.user_01.pubkey(),
+            &Vote::get_vote_pubkey(&env.realm, &vote_seed),
+            Direction::Against,
+        )],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let acc = env
+        .ctx
+        .banks_client
+        .get_account(Vote::get_vote_pubkey(&env.realm, &vote_seed))
+        .await
+        .unwrap()
+        .unwrap();
+    let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote.all_votes_for, 0);
+    assert_eq!(vote.all_votes_against, 1);
+}
+
+// test user (not admin) try to delete vote
+#[should_panic]
+#[tokio::test]
+async fn not_admin() {
+    let mut env = Env::new().await;
+    let vote_seed = Pubkey::new_unique();
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::create_vote(
+            &env.admin.pubkey(),
+            &env.realm,
+            &vote_seed,
+            VoteConfig::default(),
+        )],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::delete(
+            &env.user_01.pubkey(),
+            &env.realm,
+            &Vote::get_vote_pubkey(&env.realm, &vote_seed),
+        )],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+}
+
+// test of vote create
+#[tokio::test]
+async fn test_create_vote() {
+    let mut env = Env::new().await;
+
+    let vote_seed = Pubkey::new_unique();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::create_vote(
+            &env.admin.pubkey(),
+            &env.realm,
+            &vote_seed,
+            VoteConfig::default(),
+        )],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let acc = env
+        .ctx
+        .banks_client
+        .get_account(Vote::get_vote_pubkey(&env.realm, &vote_seed))
+        .await
+        .unwrap()
+        .unwrap();
+    let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote.admin, env.admin.pubkey().to_bytes());
+}
+
+// test settings update by authority
+#[tokio::test]
+async fn test_update_settings() {
+    let mut env = Env::new().await;
+    let settings = Settings { max_votes: 1, time_to_live: 20, ..Settings::default() };
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::update_settings(&env.admin.pubkey(), &env.realm, settings.clone())],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let acc = env.ctx.banks_client.get_account(env.realm).await.unwrap().unwrap();
+    let vote_counter = VoteCounter::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote_counter.settings, settings);
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::create_vote(
+            &env.admin.pubkey(),
+            &env.realm,
+            &Pubkey::new_unique(),
+            VoteConfig::default(),
+        )],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::create_vote(
+            &env.admin.pubkey(),
+            &env.realm,
+            &Pubkey::new_unique(),
+            VoteConfig::default(),
+        )],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+}
+
+// test user (not authority) try to update settings
+#[should_panic]
+#[tokio::test]
+async fn not_authority() {
+    let mut env = Env::new().await;
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::update_settings(&env.user_01.pubkey(), &env.realm, Settings::default())],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+}
+
+// test of poll with several options
+#[tokio::test]
+async fn test_poll() {
+    let mut env = Env::new().await;
+    let vote_seed = Pubkey::new_unique();
+    let config = VoteConfig {
+        options: vec!["Monday".to_string(), "Wednesday".to_string(), "Friday".to_string()],
+        ..VoteConfig::default()
+    };
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::create_vote(&env.admin.pubkey(), &env.realm, &vote_seed, config)],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote(
+            &env.user_01.pubkey(),
+            &Vote::get_vote_pubkey(&env.realm, &vote_seed),
+            Direction::Choice(2),
+        )],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote(
+            &env.user_02.pubkey(),
+            &Vote::get_vote_pubkey(&env.realm, &vote_seed),
+            Direction::Choice(3),
+        )],
+        Some(&env.user_02.pubkey()),
+        &[&env.user_02],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote(
+            &env.user_03.pubkey(),
+            &Vote::get_vote_pubkey(&env.realm, &vote_seed),
+            Direction::For,
+        )],
+        Some(&env.user_03.pubkey()),
+        &[&env.user_03],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let acc = env
+        .ctx
+        .banks_client
+        .get_account(Vote::get_vote_pubkey(&env.realm, &vote_seed))
+        .await
+        .unwrap()
+        .unwrap();
+    let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote.option_votes, vec![0, 0, 1]);
+}
+
+// test realms have independent counters and settings
+#[tokio::test]
+async fn test_realms() {
+    let mut env = Env::new().await;
+    let other_realm = VoteCounter::get_vote_pubkey("other");
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::create_realm(
+            &env.user_01.pubkey(),
+            "other",
+            Settings { max_votes: 1, ..Settings::default() },
+        )],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let vote_seed = Pubkey::new_unique();
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::create_vote(
+                &env.user_01.pubkey(),
+                &other_realm,
+                &vote_seed,
+                VoteConfig::default(),
+            ),
+            VoteInstruction::create_vote(
+                &env.user_01.pubkey(),
+                &env.realm,
+                &vote_seed,
+                VoteConfig::default(),
+            ),
+        ],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::create_vote(
+            &env.user_01.pubkey(),
+            &other_realm,
+            &Pubkey::new_unique(),
+            VoteConfig::default(),
+        )],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    env.ctx.warp_to_slot(11).unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::finalize(
+            &env.user_01.pubkey(),
+            &env.realm,
+            &Vote::get_vote_pubkey(&other_realm, &vote_seed),
+        )],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let acc = env.ctx.banks_client.get_account(env.realm).await.unwrap().unwrap();
+    let vote_counter = VoteCounter::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote_counter.counter, 1);
+
+    let acc = env.ctx.banks_client.get_account(other_realm).await.unwrap().unwrap();
+    let vote_counter = VoteCounter::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote_counter.counter, 1);
+    assert_eq!(vote_counter.name, "other");
+}
+
+// test of token-weighted vote with deposits locked until ballots are released
+#[tokio::test]
+async fn test_token_weighted_vote() {
+    let mut env = Env::new().await;
+    let mint = env.create_mint().await;
+    let user_01_tokens = env.create_token_account(&mint, &env.user_01.pubkey(), 100).await;
+    let user_02_tokens = env.create_token_account(&mint, &env.user_02.pubkey(), 30).await;
+
+    for (user, token_account, amount) in
+        [(&env.user_01, &user_01_tokens, 100), (&env.user_02, &user_02_tokens, 30)]
+    {
+        let tx = Transaction::new_signed_with_payer(
+            &[VoteInstruction::deposit(&user.pubkey(), &mint, token_account, amount, 0)],
+            Some(&user.pubkey()),
+            &[user],
+            env.ctx.last_blockhash,
+        );
+        env.ctx.banks_client.process_transaction(tx).await.unwrap();
+    }
+
+    let vote_seed = Pubkey::new_unique();
+    let vote_pubkey = Vote::get_vote_pubkey(&env.realm, &vote_seed);
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::create_vote(
+            &env.admin.pubkey(),
+            &env.realm,
+            &vote_seed,
+            VoteConfig { governing_mint: Some(mint), ..VoteConfig::default() },
+        )],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    for (user, direction) in [(&env.user_01, Direction::For), (&env.user_02, Direction::Against)] {
+        let tx = Transaction::new_signed_with_payer(
+            &[VoteInstruction::vote_with_deposit(&user.pubkey(), &vote_pubkey, &mint, direction)],
+            Some(&user.pubkey()),
+            &[user],
+            env.ctx.last_blockhash,
+        );
+        env.ctx.banks_client.process_transaction(tx).await.unwrap();
+    }
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote_with_deposit(
+            &env.user_03.pubkey(),
+            &vote_pubkey,
+            &mint,
+            Direction::Against,
+        )],
+        Some(&env.user_03.pubkey()),
+        &[&env.user_03],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let acc = env.ctx.banks_client.get_account(vote_pubkey).await.unwrap().unwrap();
+    let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote.all_votes_for, 100);
+    assert_eq!(vote.all_votes_against, 30);
+
+    let acc = env
+        .ctx
+        .banks_client
+        .get_account(UserVotes::get_uservote_pubkey(&env.user_01.pubkey(), &vote_pubkey))
+        .await
+        .unwrap()
+        .unwrap();
+    let user_votes = UserVotes::try_from_slice(&acc.data).unwrap();
+    assert_eq!(user_votes.weight, 100);
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::withdraw(&env.user_01.pubkey(), &mint, &user_01_tokens, 100)],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    env.warp_past_lifetime(11).await;
+
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::finalize(&env.user_01.pubkey(), &env.realm, &vote_pubkey),
+            VoteInstruction::close_user_vote_with_deposit(
+                &env.user_01.pubkey(),
+                &vote_pubkey,
+                &mint,
+            ),
+            VoteInstruction::withdraw(&env.user_01.pubkey(), &mint, &user_01_tokens, 100),
+        ],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let acc = env.ctx.banks_client.get_account(user_01_tokens).await.unwrap().unwrap();
+    let token_account = spl_token::state::Account::unpack(&acc.data).unwrap();
+    assert_eq!(token_account.amount, 100);
+
+    let acc = env
+        .ctx
+        .banks_client
+        .get_account(VoterRecord::get_voter_record_pubkey(&env.user_01.pubkey(), &mint))
+        .await
+        .unwrap()
+        .unwrap();
+    let voter_record = VoterRecord::try_from_slice(&acc.data).unwrap();
+    assert_eq!(voter_record.amount, 0);
+    assert_eq!(voter_record.active_votes, 0);
+}
+
+// test of voting power bonus for locked deposit
+#[tokio::test]
+async fn test_lockup_voting_power() {
+    let mut env = Env::new().await;
+    let mint = env.create_mint().await;
+    let user_01_tokens = env.create_token_account(&mint, &env.user_01.pubkey(), 100).await;
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::deposit(
+            &env.user_01.pubkey(),
+            &mint,
+            &user_01_tokens,
+            100,
+            MAX_LOCKUP_SECS / 2,
+        )],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let vote_seed = Pubkey::new_unique();
+    let vote_pubkey = Vote::get_vote_pubkey(&env.realm, &vote_seed);
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::create_vote(
+                &env.admin.pubkey(),
+                &env.realm,
+                &vote_seed,
+                VoteConfig { governing_mint: Some(mint), ..VoteConfig::default() },
+            ),
+            VoteInstruction::vote_with_deposit(
+                &env.user_01.pubkey(),
+                &vote_pubkey,
+                &mint,
+                Direction::For,
+            ),
+        ],
+        Some(&env.admin.pubkey()),
+        &[&env.admin, &env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let acc = env.ctx.banks_client.get_account(vote_pubkey).await.unwrap().unwrap();
+    let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote.all_votes_for, 250);
+
+    env.warp_past_lifetime(11).await;
+
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::finalize(&env.user_01.pubkey(), &env.realm, &vote_pubkey),
+            VoteInstruction::close_user_vote_with_deposit(
+                &env.user_01.pubkey(),
+                &vote_pubkey,
+                &mint,
+            ),
+        ],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::withdraw(&env.user_01.pubkey(), &mint, &user_01_tokens, 100)],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    env.ctx.warp_to_slot(12).unwrap();
+    let mut clock = env.ctx.banks_client.get_sysvar::<Clock>().await.unwrap();
+    clock.unix_timestamp += MAX_LOCKUP_SECS as i64;
+    env.ctx.set_sysvar(&clock);
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::withdraw(&env.user_01.pubkey(), &mint, &user_01_tokens, 100)],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+}
+
+// test voting power of large deposit saturates instead of overflowing
+#[tokio::test]
+async fn test_voting_power_saturates() {
+    let mut env = Env::new().await;
+    let mint = env.create_mint().await;
+    let amount = u64::MAX / 2;
+    let user_01_tokens = env.create_token_account(&mint, &env.user_01.pubkey(), amount).await;
+    let vote_seed = Pubkey::new_unique();
+    let vote_pubkey = Vote::get_vote_pubkey(&env.realm, &vote_seed);
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::deposit(
+                &env.user_01.pubkey(),
+                &mint,
+                &user_01_tokens,
+                amount,
+                MAX_LOCKUP_SECS,
+            ),
+            VoteInstruction::create_vote(
+                &env.admin.pubkey(),
+                &env.realm,
+                &vote_seed,
+                VoteConfig { governing_mint: Some(mint), ..VoteConfig::default() },
+            ),
+            VoteInstruction::vote_with_deposit(
+                &env.user_01.pubkey(),
+                &vote_pubkey,
+                &mint,
+                Direction::For,
+            ),
+        ],
+        Some(&env.admin.pubkey()),
+        &[&env.admin, &env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let acc = env.ctx.banks_client.get_account(vote_pubkey).await.unwrap().unwrap();
+    let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote.all_votes_for, u64::MAX);
+    assert_eq!(vote.outcome(), VoteStatus::Passed);
+}
+
+// test of quadratic vote counting square root of spent credits
+#[tokio::test]
+async fn test_quadratic_vote() {
+    let mut env = Env::new().await;
+    let vote_seed = Pubkey::new_unique();
+    let vote_pubkey = Vote::get_vote_pubkey(&env.realm, &vote_seed);
+
+    // quadratic vote without voice credits can't count any ballot
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::create_vote(
+            &env.admin.pubkey(),
+            &env.realm,
+            &vote_seed,
+            VoteConfig { mode: VotingMode::Quadratic, ..VoteConfig::default() },
+        )],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::create_vote(
+            &env.admin.pubkey(),
+            &env.realm,
+            &vote_seed,
+            VoteConfig { mode: VotingMode::Quadratic, voice_credits: 100, ..VoteConfig::default() },
+        )],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote_with_credits(
+            &env.user_01.pubkey(),
+            &vote_pubkey,
+            Direction::For,
+            49,
+        )],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote_with_credits(
+            &env.user_02.pubkey(),
+            &vote_pubkey,
+            Direction::Against,
+            101,
+        )],
+        Some(&env.user_02.pubkey()),
+        &[&env.user_02],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote_with_credits(
+            &env.user_02.pubkey(),
+            &vote_pubkey,
+            Direction::Against,
+            100,
+        )],
+        Some(&env.user_02.pubkey()),
+        &[&env.user_02],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote(&env.user_03.pubkey(), &vote_pubkey, Direction::For)],
+        Some(&env.user_03.pubkey()),
+        &[&env.user_03],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let acc = env.ctx.banks_client.get_account(vote_pubkey).await.unwrap().unwrap();
+    let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote.all_votes_for, 7);
+    assert_eq!(vote.all_votes_against, 10);
+
+    let acc = env
+        .ctx
+        .banks_client
+        .get_account(UserVotes::get_uservote_pubkey(&env.user_01.pubkey(), &vote_pubkey))
+        .await
+        .unwrap()
+        .unwrap();
+    let user_votes = UserVotes::try_from_slice(&acc.data).unwrap();
+    assert_eq!(user_votes.credits_spent, 49);
+    assert_eq!(user_votes.weight, 7);
+}
+
+// test instant runoff moves ballots of eliminated option to next preference
+#[tokio::test]
+async fn test_ranked_vote() {
+    let mut env = Env::new().await;
+    let vote_seed = Pubkey::new_unique();
+    let vote_pubkey = Vote::get_vote_pubkey(&env.realm, &vote_seed);
+    let config = VoteConfig {
+        options: vec!["Alice".to_string(), "Bob".to_string(), "Carol".to_string()],
+        mode: VotingMode::Ranked,
+        ..VoteConfig::default()
+    };
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::create_vote(&env.admin.pubkey(), &env.realm, &vote_seed, config)],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote(
+            &env.user_01.pubkey(),
+            &vote_pubkey,
+            Direction::Ranking(vec![0, 0]),
+        )],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote(&env.user_01.pubkey(), &vote_pubkey, Direction::Choice(0))],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    for (user, ranking) in
+        [(&env.user_01, vec![0, 1]), (&env.user_02, vec![1, 0]), (&env.user_03, vec![2, 1])]
+    {
+        let tx = Transaction::new_signed_with_payer(
+            &[VoteInstruction::vote(&user.pubkey(), &vote_pubkey, Direction::Ranking(ranking))],
+            Some(&user.pubkey()),
+            &[user],
+            env.ctx.last_blockhash,
+        );
+        env.ctx.banks_client.process_transaction(tx).await.unwrap();
+    }
+
+    let voters = [env.user_01.pubkey(), env.user_02.pubkey(), env.user_03.pubkey()];
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::tally(&vote_pubkey, &voters)],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    env.ctx.warp_to_slot(11).unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::finalize(&env.admin.pubkey(), &env.realm, &vote_pubkey)],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    for batch in [&voters[..2], &voters[2..], &voters[..]] {
+        let tx = Transaction::new_signed_with_payer(
+            &[VoteInstruction::tally(&vote_pubkey, batch)],
+            Some(&env.admin.pubkey()),
+            &[&env.admin],
+            env.ctx.last_blockhash,
+        );
+        env.ctx.banks_client.process_transaction(tx).await.unwrap();
+    }
+
+    let acc = env.ctx.banks_client.get_account(vote_pubkey).await.unwrap().unwrap();
+    let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    let tally = vote.ranked_tally.unwrap();
+    assert_eq!(vote.status, VoteStatus::Passed);
+    assert_eq!(vote.option_votes, vec![1, 1, 1]);
+    assert_eq!(tally.winner, 1);
+    assert_eq!(tally.round, 1);
+    assert_eq!(tally.rounds[0], vec![1, 1, 1]);
+    assert_eq!(tally.rounds[1], vec![1, 2, 0]);
+}
+
+// test approval ballot counts once for every approved option
+#[tokio::test]
+async fn test_approval_vote() {
+    let mut env = Env::new().await;
+    let vote_seed = Pubkey::new_unique();
+    let vote_pubkey = Vote::get_vote_pubkey(&env.realm, &vote_seed);
+    let config = VoteConfig {
+        options: vec!["Red".to_string(), "Green".to_string(), "Blue".to_string()],
+        mode: VotingMode::Approval,
+        ..VoteConfig::default()
+    };
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::create_vote(&env.admin.pubkey(), &env.realm, &vote_seed, config)],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    for (user, approvals) in [(&env.user_01, 0b011), (&env.user_02, 0b110)] {
+        let tx = Transaction::new_signed_with_payer(
+            &[VoteInstruction::vote(&user.pubkey(), &vote_pubkey, Direction::Approve(approvals))],
+            Some(&user.pubkey()),
+            &[user],
+            env.ctx.last_blockhash,
+        );
+        env.ctx.banks_client.process_transaction(tx).await.unwrap();
+    }
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote(&env.user_03.pubkey(), &vote_pubkey, Direction::Approve(0b1001))],
+        Some(&env.user_03.pubkey()),
+        &[&env.user_03],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let acc = env
+        .ctx
+        .banks_client
+        .get_account(UserVotes::get_uservote_pubkey(&env.user_02.pubkey(), &vote_pubkey))
+        .await
+        .unwrap()
+        .unwrap();
+    let participation = UserVotes::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(participation.approvals, 0b110);
+
+    env.ctx.warp_to_slot(11).unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::finalize(&env.admin.pubkey(), &env.realm, &vote_pubkey)],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let acc = env.ctx.banks_client.get_account(vote_pubkey).await.unwrap().unwrap();
+    let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote.option_votes, vec![1, 2, 1]);
+    assert_eq!(vote.all_ballots, 2);
+    assert_eq!(vote.status, VoteStatus::Passed);
+}
+
+// test score ballots sum per option and count toward mean
+#[tokio::test]
+async fn test_score_vote() {
+    let mut env = Env::new().await;
+    let vote_seed = Pubkey::new_unique();
+    let vote_pubkey = Vote::get_vote_pubkey(&env.realm, &vote_seed);
+    let config = VoteConfig {
+        options: vec!["Pace".to_string(), "Scope".to_string()],
+        mode: VotingMode::Score,
+        max_score: 5,
+        ..VoteConfig::default()
+    };
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::create_vote(&env.admin.pubkey(), &env.realm, &vote_seed, config)],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    for (user, scores) in [(&env.user_01, vec![5, 2]), (&env.user_02, vec![4, 0])] {
+        let tx = Transaction::new_signed_with_payer(
+            &[VoteInstruction::vote(&user.pubkey(), &vote_pubkey, Direction::Scores(scores))],
+            Some(&user.pubkey()),
+            &[user],
+            env.ctx.last_blockhash,
+        );
+        env.ctx.banks_client.process_transaction(tx).await.unwrap();
+    }
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote(
+            &env.user_03.pubkey(),
+            &vote_pubkey,
+            Direction::Scores(vec![6, 0]),
+        )],
+        Some(&env.user_03.pubkey()),
+        &[&env.user_03],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote(&env.user_03.pubkey(), &vote_pubkey, Direction::Scores(vec![3]))],
+        Some(&env.user_03.pubkey()),
+        &[&env.user_03],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let acc = env.ctx.banks_client.get_account(vote_pubkey).await.unwrap().unwrap();
+    let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote.option_votes, vec![9, 2]);
+    assert_eq!(vote.all_ballots, 2);
+}
+
+// test retracted ballot is taken back from tally and can be cast again
+#[tokio::test]
+async fn test_retract_vote() {
+    let mut env = Env::new().await;
+    let vote_seed = Pubkey::new_unique();
+    let vote_pubkey = Vote::get_vote_pubkey(&env.realm, &vote_seed);
+    let config = VoteConfig {
+        options: vec!["Tea".to_string(), "Coffee".to_string()],
+        mode: VotingMode::Approval,
+        ..VoteConfig::default()
+    };
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::create_vote(&env.admin.pubkey(), &env.realm, &vote_seed, config)],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::retract_vote(&env.user_01.pubkey(), &vote_pubkey)],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::vote(&env.user_01.pubkey(), &vote_pubkey, Direction::Approve(0b11)),
+            VoteInstruction::retract_vote(&env.user_01.pubkey(), &vote_pubkey),
+        ],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let acc = env.ctx.banks_client.get_account(vote_pubkey).await.unwrap().unwrap();
+    let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote.option_votes, vec![0, 0]);
+    assert_eq!(vote.all_ballots, 0);
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote(&env.user_01.pubkey(), &vote_pubkey, Direction::Approve(0b10))],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let acc = env.ctx.banks_client.get_account(vote_pubkey).await.unwrap().unwrap();
+    let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote.option_votes, vec![0, 1]);
+    assert_eq!(vote.all_ballots, 1);
+
+    env.ctx.warp_to_slot(11).unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::finalize(&env.admin.pubkey(), &env.realm, &vote_pubkey)],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::retract_vote(&env.user_01.pubkey(), &vote_pubkey)],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+}
+
+// test only allowlisted voters can vote, with weight from allowlist
+#[tokio::test]
+async fn test_allowlist() {
+    let mut env = Env::new().await;
+    let vote_seed = Pubkey::new_unique();
+    let vote_pubkey = Vote::get_vote_pubkey(&env.realm, &vote_seed);
+    let leaf_01 = allowlist_leaf(&env.user_01.pubkey(), 2);
+    let leaf_02 = allowlist_leaf(&env.user_02.pubkey(), 1);
+    let leaf_03 = allowlist_leaf(&env.user_03.pubkey(), 1);
+    let node_12 = allowlist_node(&leaf_01, &leaf_02);
+    let config = VoteConfig {
+        voter_root: Some(allowlist_node(&node_12, &leaf_03)),
+        ..VoteConfig::default()
+    };
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::create_vote(&env.admin.pubkey(), &env.realm, &vote_seed, config)],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote_with_proof(
+            &env.user_01.pubkey(),
+            &vote_pubkey,
+            Direction::For,
+            None,
+            VoterProof { weight: 2, path: vec![leaf_02, leaf_03] },
+        )],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote_with_proof(
+            &env.user_03.pubkey(),
+            &vote_pubkey,
+            Direction::Against,
+            None,
+            VoterProof { weight: 1, path: vec![node_12] },
+        )],
+        Some(&env.user_03.pubkey()),
+        &[&env.user_03],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote_with_proof(
+            &env.user_02.pubkey(),
+            &vote_pubkey,
+            Direction::For,
+            None,
+            VoterProof { weight: 5, path: vec![leaf_01, leaf_03] },
+        )],
+        Some(&env.user_02.pubkey()),
+        &[&env.user_02],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote(&env.admin.pubkey(), &vote_pubkey, Direction::For)],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let acc = env.ctx.banks_client.get_account(vote_pubkey).await.unwrap().unwrap();
+    let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote.all_votes_for, 2);
+    assert_eq!(vote.all_votes_against, 1);
+}
+
+// test members-only vote accepts ballots only from voters added by admin
+#[tokio::test]
+async fn test_members_only() {
+    let mut env = Env::new().await;
+    let vote_seed = Pubkey::new_unique();
+    let vote_pubkey = Vote::get_vote_pubkey(&env.realm, &vote_seed);
+    let config = VoteConfig { members_only: true, ..VoteConfig::default() };
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::create_vote(&env.admin.pubkey(), &env.realm, &vote_seed, config),
+            VoteInstruction::add_voter(
+                &env.admin.pubkey(),
+                &env.realm,
+                &vote_pubkey,
+                &env.user_01.pubkey(),
+            ),
+        ],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::add_voter(
+            &env.user_02.pubkey(),
+            &env.realm,
+            &vote_pubkey,
+            &env.user_02.pubkey(),
+        )],
+        Some(&env.user_02.pubkey()),
+        &[&env.user_02],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote_as_member(&env.user_01.pubkey(), &vote_pubkey, Direction::For)],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote_as_member(&env.user_02.pubkey(), &vote_pubkey, Direction::For)],
+        Some(&env.user_02.pubkey()),
+        &[&env.user_02],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::remove_voter(
+                &env.admin.pubkey(),
+                &env.realm,
+                &vote_pubkey,
+                &env.user_01.pubkey(),
+            ),
+            VoteInstruction::vote_as_member(
+                &env.user_01.pubkey(),
+                &vote_pubkey,
+                Direction::Against,
+            ),
+        ],
+        Some(&env.admin.pubkey()),
+        &[&env.admin, &env.user_01],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let acc = env.ctx.banks_client.get_account(vote_pubkey).await.unwrap().unwrap();
+    let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote.all_votes_for, 1);
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::remove_voter(
+            &env.admin.pubkey(),
+            &env.realm,
+            &vote_pubkey,
+            &env.user_01.pubkey(),
+        )],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let acc = env
+        .ctx
+        .banks_client
+        .get_account(Membership::get_membership_pubkey(&vote_pubkey, &env.user_01.pubkey()))
+        .await
+        .unwrap();
+    assert!(acc.is_none());
+}
+
+// test collection-gated ballot belongs to NFT and follows it to new holder
+#[tokio::test]
+async fn test_nft_vote() {
+    let mut env = Env::new().await;
+    let collection = Pubkey::new_unique();
+    let vote_seed = Pubkey::new_unique();
+    let vote_pubkey = Vote::get_vote_pubkey(&env.realm, &vote_seed);
+    let config = VoteConfig { collection: Some(collection), ..VoteConfig::default() };
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::create_vote(&env.admin.pubkey(), &env.realm, &vote_seed, config)],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let user_01 = env.user_01.pubkey();
+    let user_02 = env.user_02.pubkey();
+    let (nft, nft_account_01) =
+        env.create_nft(&user_01, Collection { verified: true, key: collection }).await;
+    let (fake_nft, fake_account) =
+        env.create_nft(&user_01, Collection { verified: false, key: collection }).await;
+    let nft_account_02 = env.create_token_account(&nft, &user_02, 0).await;
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote_with_nft(
+            &user_01,
+            &vote_pubkey,
+            &nft,
+            &nft_account_01,
+            Direction::For,
+        )],
+        Some(&user_01),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote_with_nft(
+            &user_01,
+            &vote_pubkey,
+            &fake_nft,
+            &fake_account,
+            Direction::For,
+        )],
+        Some(&user_01),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            spl_token::instruction::transfer(
+                &spl_token::id(),
+                &nft_account_01,
+                &nft_account_02,
+                &user_01,
+                &[],
+                1,
+            )
+            .unwrap(),
+            VoteInstruction::vote_with_nft(
+                &user_02,
+                &vote_pubkey,
+                &nft,
+                &nft_account_02,
+                Direction::Against,
+            ),
+        ],
+        Some(&user_01),
+        &[&env.user_01, &env.user_02],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote_with_nft(
+            &user_01,
+            &vote_pubkey,
+            &nft,
+            &nft_account_01,
+            Direction::Against,
+        )],
+        Some(&user_01),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let acc = env.ctx.banks_client.get_account(vote_pubkey).await.unwrap().unwrap();
+    let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote.all_votes_for, 0);
+    assert_eq!(vote.all_votes_against, 1);
+}
+
+// test delegate casts ballots for delegators unless they voted themselves
+#[tokio::test]
+async fn test_delegation() {
+    let mut env = Env::new().await;
+    let vote_seed = Pubkey::new_unique();
+    let vote_pubkey = Vote::get_vote_pubkey(&env.realm, &vote_seed);
+    let user_01 = env.user_01.pubkey();
+    let user_02 = env.user_02.pubkey();
+    let user_03 = env.user_03.pubkey();
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::create_vote(
+                &env.admin.pubkey(),
+                &env.realm,
+                &vote_seed,
+                VoteConfig::default(),
+            ),
+            VoteInstruction::delegate(&user_01, &env.realm, &user_03),
+            VoteInstruction::delegate(&user_02, &env.realm, &user_03),
+            VoteInstruction::vote(&user_02, &vote_pubkey, Direction::For),
+        ],
+        Some(&env.admin.pubkey()),
+        &[&env.admin, &env.user_01, &env.user_02],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote_for_delegators(
+            &user_03,
+            &env.realm,
+            &vote_pubkey,
+            Direction::Against,
+            &[user_01, user_02],
+        )],
+        Some(&user_03),
+        &[&env.user_03],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let acc = env.ctx.banks_client.get_account(vote_pubkey).await.unwrap().unwrap();
+    let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote.all_votes_for, 1);
+    assert_eq!(vote.all_votes_against, 2);
+
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::vote(&user_01, &vote_pubkey, Direction::For),
+            VoteInstruction::revoke_delegation(&user_01, &env.realm),
+        ],
+        Some(&user_01),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let acc = env.ctx.banks_client.get_account(vote_pubkey).await.unwrap().unwrap();
+    let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote.all_votes_for, 2);
+    assert_eq!(vote.all_votes_against, 1);
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote_for_delegators(
+            &user_03,
+            &env.realm,
+            &vote_pubkey,
+            Direction::Abstain,
+            &[user_01],
+        )],
+        Some(&user_03),
+        &[&env.user_03],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::delegate(&user_01, &env.realm, &user_01)],
+        Some(&user_01),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+}
+
+// test committed ballots are counted only once revealed
+#[tokio::test]
+async fn test_commit_reveal() {
+    let mut env = Env::new().await;
+    let vote_seed = Pubkey::new_unique();
+    let vote_pubkey = Vote::get_vote_pubkey(&env.realm, &vote_seed);
+    let now = env.ctx.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp;
+    let config = VoteConfig {
+        end_ts: Some(now + 100),
+        reveal_end_ts: Some(now + 200),
+        ..VoteConfig::default()
+    };
+    let salt_01 = [1; 32];
+    let salt_02 = [2; 32];
+    let commitment_01 = Direction::For.commitment(&vote_pubkey, &env.user_01.pubkey(), &salt_01);
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::create_vote(&env.admin.pubkey(), &env.realm, &vote_seed, config),
+            VoteInstruction::vote(
+                &env.user_01.pubkey(),
+                &vote_pubkey,
+                Direction::Commit(commitment_01),
+            ),
+            VoteInstruction::vote(
+                &env.user_02.pubkey(),
+                &vote_pubkey,
+                Direction::Commit(Direction::Against.commitment(
+                    &vote_pubkey,
+                    &env.user_02.pubkey(),
+                    &salt_02,
+                )),
+            ),
+            // user_03 copies commitment of user_01 to reveal it later
+            VoteInstruction::vote(
+                &env.user_03.pubkey(),
+                &vote_pubkey,
+                Direction::Commit(commitment_01),
+            ),
+        ],
+        Some(&env.admin.pubkey()),
+        &[&env.admin, &env.user_01, &env.user_02, &env.user_03],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote(&env.admin.pubkey(), &vote_pubkey, Direction::For)],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::reveal(&env.user_01.pubkey(), &vote_pubkey, Direction::For, salt_01)],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    env.ctx.warp_to_slot(11).unwrap();
+    let mut clock = env.ctx.banks_client.get_sysvar::<Clock>().await.unwrap();
+    clock.unix_timestamp = now + 150;
+    env.ctx.set_sysvar(&clock);
+
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::reveal(&env.user_01.pubkey(), &vote_pubkey, Direction::For, salt_01),
+            VoteInstruction::reveal(
+                &env.user_02.pubkey(),
+                &vote_pubkey,
+                Direction::Against,
+                salt_02,
+            ),
+        ],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01, &env.user_02],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::reveal(&env.user_03.pubkey(), &vote_pubkey, Direction::For, salt_01)],
+        Some(&env.user_03.pubkey()),
+        &[&env.user_03],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    env.ctx.warp_to_slot(12).unwrap();
+    let mut clock = env.ctx.banks_client.get_sysvar::<Clock>().await.unwrap();
+    clock.unix_timestamp = now + 200;
+    env.ctx.set_sysvar(&clock);
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::finalize(&env.admin.pubkey(), &env.realm, &vote_pubkey)],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let acc = env.ctx.banks_client.get_account(vote_pubkey).await.unwrap().unwrap();
+    let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote.all_votes_for, 1);
+    assert_eq!(vote.all_votes_against, 1);
+    assert_eq!(vote.unrevealed, 1);
+    assert_eq!(vote.status, VoteStatus::Rejected);
+}
+
+// test anonymous ballot is recorded by nullifier and cast once per voter key
+#[tokio::test]
+async fn test_anonymous_vote() {
+    let mut env = Env::new().await;
+    let vote_seed = Pubkey::new_unique();
+    let vote_pubkey = Vote::get_vote_pubkey(&env.realm, &vote_seed);
+    let voter_key_01 = Keypair::new();
+    let voter_key_02 = Keypair::new();
+    let outsider_key = Keypair::new();
+    let leaf_01 = anonymous_leaf(&voter_key_01.pubkey());
+    let leaf_02 = anonymous_leaf(&voter_key_02.pubkey());
+    let config = VoteConfig {
+        anonymous_root: Some(allowlist_node(&leaf_01, &leaf_02)),
+        ..VoteConfig::default()
+    };
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::create_vote(&env.admin.pubkey(), &env.realm, &vote_seed, config)],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::vote_anonymous(
+                &env.user_01.pubkey(),
+                &vote_pubkey,
+                Direction::For,
+                &voter_key_01.pubkey(),
+                vec![leaf_02],
+            ),
+            VoteInstruction::vote_anonymous(
+                &env.user_01.pubkey(),
+                &vote_pubkey,
+                Direction::Against,
+                &voter_key_02.pubkey(),
+                vec![leaf_01],
+            ),
+        ],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01, &voter_key_01, &voter_key_02],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote_anonymous(
+            &env.user_02.pubkey(),
+            &vote_pubkey,
+            Direction::Against,
+            &voter_key_01.pubkey(),
+            vec![leaf_02],
+        )],
+        Some(&env.user_02.pubkey()),
+        &[&env.user_02, &voter_key_01],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote_anonymous(
+            &env.user_02.pubkey(),
+            &vote_pubkey,
+            Direction::For,
+            &outsider_key.pubkey(),
+            vec![leaf_02],
+        )],
+        Some(&env.user_02.pubkey()),
+        &[&env.user_02, &outsider_key],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote(&env.user_03.pubkey(), &vote_pubkey, Direction::For)],
+        Some(&env.user_03.pubkey()),
+        &[&env.user_03],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let acc = env.ctx.banks_client.get_account(vote_pubkey).await.unwrap().unwrap();
+    let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote.all_votes_for, 1);
+    assert_eq!(vote.all_votes_against, 1);
+
+    let nullifier = anonymous_nullifier(&voter_key_01.pubkey(), &vote_pubkey);
+    let acc = env
+        .ctx
+        .banks_client
+        .get_account(UserVotes::get_nullifier_pubkey(&nullifier, &vote_pubkey))
+        .await
+        .unwrap()
+        .unwrap();
+    let participation = UserVotes::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(participation.ballot, BallotKind::For);
+
+    // nullifier PDA is closed by its payer once vote is not alive
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::close_nullifier(&env.user_01.pubkey(), &vote_pubkey, nullifier)],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    env.ctx.warp_to_slot(11).unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::finalize(&env.admin.pubkey(), &env.realm, &vote_pubkey),
+            VoteInstruction::close_nullifier(&env.user_02.pubkey(), &vote_pubkey, nullifier),
+        ],
+        Some(&env.admin.pubkey()),
+        &[&env.admin, &env.user_02],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::finalize(&env.admin.pubkey(), &env.realm, &vote_pubkey),
+            VoteInstruction::close_nullifier(&env.user_01.pubkey(), &vote_pubkey, nullifier),
+        ],
+        Some(&env.admin.pubkey()),
+        &[&env.admin, &env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let nullifier_pubkey = UserVotes::get_nullifier_pubkey(&nullifier, &vote_pubkey);
+    assert!(env.ctx.banks_client.get_account(nullifier_pubkey).await.unwrap().is_none());
+}
+
+fn relay_signature(voter: &Keypair, vote: &Pubkey, direction: &Direction, nonce: u64) -> Vec<u8> {
+    let message = direction.relay_message(vote, nonce);
+    voter.sign_message(&message).as_ref().to_vec()
+}
+
+// test ballot signed off-chain is cast by relayer and can't be replayed
+#[tokio::test]
+async fn test_relay_vote() {
+    let mut env = Env::new().await;
+    let vote_seed = Pubkey::new_unique();
+    let vote_pubkey = Vote::get_vote_pubkey(&env.realm, &vote_seed);
+    let voter = Keypair::new();
+    let outsider = Keypair::new();
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::create_vote(
+            &env.admin.pubkey(),
+            &env.realm,
+            &vote_seed,
+            VoteConfig::default(),
+        )],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let signature = relay_signature(&voter, &vote_pubkey, &Direction::For, 1);
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::verify_relay_signature(
+                &voter.pubkey(),
+                &signature.clone().try_into().unwrap(),
+                &Direction::For.relay_message(&vote_pubkey, 1),
+            ),
+            VoteInstruction::relay_vote(
+                &env.user_01.pubkey(),
+                &voter.pubkey(),
+                &vote_pubkey,
+                Direction::For,
+                1,
+            ),
+        ],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    // replay of the same signed ballot by another relayer
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::verify_relay_signature(
+                &voter.pubkey(),
+                &signature.try_into().unwrap(),
+                &Direction::For.relay_message(&vote_pubkey, 1),
+            ),
+            VoteInstruction::relay_vote(
+                &env.user_02.pubkey(),
+                &voter.pubkey(),
+                &vote_pubkey,
+                Direction::For,
+                1,
+            ),
+        ],
+        Some(&env.user_02.pubkey()),
+        &[&env.user_02],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    // ballot signed by someone else on behalf of voter
+    let signature = relay_signature(&outsider, &vote_pubkey, &Direction::Against, 2);
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::verify_relay_signature(
+                &outsider.pubkey(),
+                &signature.try_into().unwrap(),
+                &Direction::Against.relay_message(&vote_pubkey, 2),
+            ),
+            VoteInstruction::relay_vote(
+                &env.user_01.pubkey(),
+                &voter.pubkey(),
+                &vote_pubkey,
+                Direction::Against,
+                2,
+            ),
+        ],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    // relayed ballot without signature check
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::relay_vote(
+            &env.user_01.pubkey(),
+            &voter.pubkey(),
+            &vote_pubkey,
+            Direction::Against,
+            2,
+        )],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    // signature over message without domain tag
+    let untagged = (vote_pubkey, Direction::Against, 2u64).try_to_vec().unwrap();
+    let signature: [u8; 64] = voter.sign_message(&untagged).as_ref().try_into().unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::verify_relay_signature(&voter.pubkey(), &signature, &untagged),
+            VoteInstruction::relay_vote(
+                &env.user_01.pubkey(),
+                &voter.pubkey(),
+                &vote_pubkey,
+                Direction::Against,
+                2,
+            ),
+        ],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let signature = relay_signature(&voter, &vote_pubkey, &Direction::Against, 2);
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::verify_relay_signature(
+                &voter.pubkey(),
+                &signature.try_into().unwrap(),
+                &Direction::Against.relay_message(&vote_pubkey, 2),
+            ),
+            VoteInstruction::relay_vote(
+                &env.user_02.pubkey(),
+                &voter.pubkey(),
+                &vote_pubkey,
+                Direction::Against,
+                2,
+            ),
+        ],
+        Some(&env.user_02.pubkey()),
+        &[&env.user_02],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let acc = env.ctx.banks_client.get_account(vote_pubkey).await.unwrap().unwrap();
+    let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote.all_votes_for, 0);
+    assert_eq!(vote.all_votes_against, 1);
+
+    let acc = env
+        .ctx
+        .banks_client
+        .get_account(UserVotes::get_uservote_pubkey(&voter.pubkey(), &vote_pubkey))
+        .await
+        .unwrap()
+        .unwrap();
+    let user_votes = UserVotes::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(user_votes.ballot, BallotKind::Against);
+    assert_eq!(user_votes.relay_nonce, 2);
+}
+
+// test ballots of batch are cast atomically and failed ballot is reported by index
+#[tokio::test]
+async fn test_vote_many() {
+    let mut env = Env::new().await;
+    let proposal_seed = Pubkey::new_unique();
+    let proposal_pubkey = Vote::get_vote_pubkey(&env.realm, &proposal_seed);
+    let poll_seed = Pubkey::new_unique();
+    let poll_pubkey = Vote::get_vote_pubkey(&env.realm, &poll_seed);
+    let poll_config = VoteConfig {
+        options: vec!["Monday".to_string(), "Wednesday".to_string(), "Friday".to_string()],
+        ..VoteConfig::default()
+    };
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::create_vote(
+                &env.admin.pubkey(),
+                &env.realm,
+                &proposal_seed,
+                VoteConfig::default(),
+            ),
+            VoteInstruction::create_vote(&env.admin.pubkey(), &env.realm, &poll_seed, poll_config),
+        ],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote_many(
+            &env.user_01.pubkey(),
+            vec![(proposal_pubkey, Direction::For), (poll_pubkey, Direction::Choice(2))],
+        )],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    // second ballot fails, so first one is not changed either
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote_many(
+            &env.user_01.pubkey(),
+            vec![(proposal_pubkey, Direction::Against), (poll_pubkey, Direction::Choice(7))],
+        )],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    let err = env.ctx.banks_client.process_transaction(tx).await.unwrap_err().unwrap();
+    assert_eq!(
+        err,
+        TransactionError::InstructionError(0, InstructionError::Custom(BATCH_ENTRY_ERROR + 1))
+    );
+
+    let acc = env.ctx.banks_client.get_account(proposal_pubkey).await.unwrap().unwrap();
+    let proposal = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(proposal.all_votes_for, 1);
+    assert_eq!(proposal.all_votes_against, 0);
+
+    let acc = env.ctx.banks_client.get_account(poll_pubkey).await.unwrap().unwrap();
+    let poll = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(poll.option_votes[2], 1);
+}
+
+// test proposal transactions run once after vote passes and timelock elapses
+#[tokio::test]
+async fn test_execute_proposal() {
+    let mut env = Env::new().await;
+    let vote_seed = Pubkey::new_unique();
+    let vote_pubkey = Vote::get_vote_pubkey(&env.realm, &vote_seed);
+    let governance = VoteCounter::get_governance_pubkey(&env.realm);
+    let recipient = env.user_03.pubkey();
+    let transfer = system_instruction::transfer(&governance, &recipient, 1_000);
+    let proposal_instruction = ProposalInstruction {
+        program_id: transfer.program_id,
+        accounts: transfer
+            .accounts
+            .iter()
+            .map(|meta| ProposalAccount {
+                pubkey: meta.pubkey,
+                is_signer: meta.is_signer,
+                is_writable: meta.is_writable,
+            })
+            .collect(),
+        data: transfer.data,
+    };
+    let config = VoteConfig { execution_delay_secs: 100, ..VoteConfig::default() };
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            system_instruction::transfer(&env.admin.pubkey(), &governance, 100_000_000),
+            VoteInstruction::create_vote(&env.admin.pubkey(), &env.realm, &vote_seed, config),
+            VoteInstruction::add_proposal_transaction(
+                &env.admin.pubkey(),
+                &env.admin.pubkey(),
+                &env.realm,
+                &vote_pubkey,
+                0,
+                proposal_instruction.clone(),
+            ),
+        ],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    // only realm authority can attach instructions signed by governance PDA
+    let outsider_seed = Pubkey::new_unique();
+    let outsider_vote = Vote::get_vote_pubkey(&env.realm, &outsider_seed);
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::create_vote(
+                &env.user_02.pubkey(),
+                &env.realm,
+                &outsider_seed,
+                VoteConfig::default(),
+            ),
+            VoteInstruction::add_proposal_transaction(
+                &env.user_02.pubkey(),
+                &env.user_02.pubkey(),
+                &env.realm,
+                &outsider_vote,
+                0,
+                proposal_instruction.clone(),
+            ),
+        ],
+        Some(&env.user_02.pubkey()),
+        &[&env.user_02],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    // passed poll does not approve instructions
+    let poll_seed = Pubkey::new_unique();
+    let poll_pubkey = Vote::get_vote_pubkey(&env.realm, &poll_seed);
+    let poll_config = VoteConfig {
+        options: vec!["Alice".to_string(), "Bob".to_string()],
+        ..VoteConfig::default()
+    };
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::create_vote(&env.admin.pubkey(), &env.realm, &poll_seed, poll_config),
+            VoteInstruction::add_proposal_transaction(
+                &env.admin.pubkey(),
+                &env.admin.pubkey(),
+                &env.realm,
+                &poll_pubkey,
+                0,
+                proposal_instruction.clone(),
+            ),
+        ],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::vote(&env.user_01.pubkey(), &vote_pubkey, Direction::For)],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::add_proposal_transaction(
+            &env.admin.pubkey(),
+            &env.admin.pubkey(),
+            &env.realm,
+            &vote_pubkey,
+            1,
+            proposal_instruction.clone(),
+        )],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::execute(&env.realm, &vote_pubkey, 0, &proposal_instruction)],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    env.warp_past_lifetime(11).await;
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::finalize(&env.user_01.pubkey(), &env.realm, &vote_pubkey)],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    // passed, but still in timelock
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::execute(&env.realm, &vote_pubkey, 0, &proposal_instruction)],
+        Some(&env.user_02.pubkey()),
+        &[&env.user_02],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let acc = env.ctx.banks_client.get_account(vote_pubkey).await.unwrap().unwrap();
+    let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote.status, VoteStatus::Passed);
+    assert_eq!(vote.proposal_transactions, 1);
+
+    // passed proposal can't be dropped before it runs
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::close_vote(&env.admin.pubkey(), &vote_pubkey, &env.admin.pubkey())],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::close_proposal_transaction(
+            &env.admin.pubkey(),
+            &env.realm,
+            &vote_pubkey,
+            0,
+        )],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    env.ctx.warp_to_slot(12).unwrap();
+    let mut clock = env.ctx.banks_client.get_sysvar::<Clock>().await.unwrap();
+    clock.unix_timestamp = vote.closed_at + 100;
+    env.ctx.set_sysvar(&clock);
+
+    let balance = env.ctx.banks_client.get_balance(recipient).await.unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::execute(&env.realm, &vote_pubkey, 0, &proposal_instruction)],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+    assert_eq!(env.ctx.banks_client.get_balance(recipient).await.unwrap(), balance + 1_000);
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::execute(&env.realm, &vote_pubkey, 0, &proposal_instruction)],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let acc = env
+        .ctx
+        .banks_client
+        .get_account(ProposalTransaction::get_proposal_transaction_pubkey(&vote_pubkey, 0))
+        .await
+        .unwrap()
+        .unwrap();
+    let proposal = ProposalTransaction::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(proposal.executed_at, vote.closed_at + 100);
+
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::close_proposal_transaction(
+                &env.admin.pubkey(),
+                &env.realm,
+                &vote_pubkey,
+                0,
+            ),
+            VoteInstruction::close_vote(&env.admin.pubkey(), &vote_pubkey, &env.admin.pubkey()),
+        ],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let proposal_pubkey = ProposalTransaction::get_proposal_transaction_pubkey(&vote_pubkey, 0);
+    assert!(env.ctx.banks_client.get_account(proposal_pubkey).await.unwrap().is_none());
+    assert!(env.ctx.banks_client.get_account(vote_pubkey).await.unwrap().unwrap().data.is_empty());
+}
+
+// test passed proposal transaction that can't run is cancelled after grace period
+#[tokio::test]
+async fn test_cancel_proposal_transaction() {
+    let mut env = Env::new().await;
+    let vote_seed = Pubkey::new_unique();
+    let vote_pubkey = Vote::get_vote_pubkey(&env.realm, &vote_seed);
+    let governance = VoteCounter::get_governance_pubkey(&env.realm);
+    let transfer = system_instruction::transfer(&governance, &env.user_03.pubkey(), 1_000);
+    let proposal_instruction = ProposalInstruction {
+        program_id: transfer.program_id,
+        accounts: transfer
+            .accounts
+            .iter()
+            .map(|meta| ProposalAccount {
+                pubkey: meta.pubkey,
+                is_signer: meta.is_signer,
+                is_writable: meta.is_writable,
+            })
+            .collect(),
+        data: transfer.data,
+    };
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::create_vote(
+                &env.user_02.pubkey(),
+                &env.realm,
+                &vote_seed,
+                VoteConfig::default(),
+            ),
+            VoteInstruction::add_proposal_transaction(
+                &env.user_02.pubkey(),
+                &env.admin.pubkey(),
+                &env.realm,
+                &vote_pubkey,
+                0,
+                proposal_instruction.clone(),
+            ),
+            VoteInstruction::vote(&env.user_01.pubkey(), &vote_pubkey, Direction::For),
+        ],
+        Some(&env.user_02.pubkey()),
+        &[&env.user_02, &env.admin, &env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    env.warp_past_lifetime(11).await;
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::finalize(&env.user_01.pubkey(), &env.realm, &vote_pubkey)],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    // governance PDA is not funded, so transfer can't run
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::execute(&env.realm, &vote_pubkey, 0, &proposal_instruction)],
+        Some(&env.user_01.pubkey()),
+        &[&env.user_01],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::close_proposal_transaction(
+            &env.admin.pubkey(),
+            &env.realm,
+            &vote_pubkey,
+            0,
+        )],
+        Some(&env.admin.pubkey()),
+        &[&env.admin],
+        env.ctx.last_blockhash,
+    );
+    let err = env.ctx.banks_client.process_transaction(tx).await.unwrap_err().unwrap();
+    assert_eq!(
+        err,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(VoteError::ProposalTransactionPending as u32)
+        )
+    );
+
+    let acc = env.ctx.banks_client.get_account(vote_pubkey).await.unwrap().unwrap();
+    let vote = Vote::try_from_slice(acc.data.as_slice()).unwrap();
+    assert_eq!(vote.status, VoteStatus::Passed);
+
+    env.ctx.warp_to_slot(12).unwrap();
+    let mut clock = env.ctx.banks_client.get_sysvar::<Clock>().await.unwrap();
+    clock.unix_timestamp = vote.closed_at + EXECUTION_GRACE_SECS as i64;
+    env.ctx.set_sysvar(&clock);
+
+    // only vote admin or realm authority can cancel
+    let tx = Transaction::new_signed_with_payer(
+        &[VoteInstruction::close_proposal_transaction(
+            &env.user_03.pubkey(),
+            &env.realm,
+            &vote_pubkey,
+            0,
+        )],
+        Some(&env.user_03.pubkey()),
+        &[&env.user_03],
+        env.ctx.last_blockhash,
+    );
+    assert!(env.ctx.banks_client.process_transaction(tx).await.is_err());
+
+    let tx = Transaction::new_signed_with_payer(
+        &[
+            VoteInstruction::close_proposal_transaction(
+                &env.admin.pubkey(),
+                &env.realm,
+                &vote_pubkey,
+                0,
+            ),
+            VoteInstruction::close_vote(&env.user_02.pubkey(), &vote_pubkey, &env.user_02.pubkey()),
+        ],
+        Some(&env.admin.pubkey()),
+        &[&env.admin, &env.user_02],
+        env.ctx.last_blockhash,
+    );
+    env.ctx.banks_client.process_transaction(tx).await.unwrap();
+
+    let proposal_pubkey = ProposalTransaction::get_proposal_transaction_pubkey(&vote_pubkey, 0);
+    assert!(env.ctx.banks_client.get_account(proposal_pubkey).await.unwrap().is_none());
+    assert!(env.ctx.banks_client.get_account(vote_pubkey).await.unwrap().unwrap().data.is_empty());
 }